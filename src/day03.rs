@@ -1,4 +1,4 @@
-use std::iter::repeat;
+use std::iter::repeat_n;
 
 const INPUT: &str = include_str!("day03.txt");
 
@@ -12,112 +12,134 @@ enum Cell {
 
 #[derive(Debug, Clone, Copy)]
 struct Number {
-    id: u16,
-    value: u16,
+    /// Flat index of the number's first digit
+    id: usize,
+    value: u64,
 }
 
-fn parse_input(input: &str) -> Vec<Vec<Cell>> {
-    let mut id_counter = 0;
-    let mut cells: Vec<Vec<_>> = input
-        .lines()
-        .map(|line| {
-            let line = line.as_bytes(); // All characters in input are ASCII
-            let mut row = Vec::with_capacity(line.len());
-
-            let mut i = 0;
-            while i < line.len() {
-                let c = line[i];
-                match c {
-                    b'.' => {
-                        row.push(Cell::Empty);
-                        i += 1;
-                    }
-                    b'0'..=b'9' => {
-                        let mut value = 0;
-                        let start = i;
-                        while i < line.len() && line[i].is_ascii_digit() {
-                            value = value * 10 + (line[i] - b'0') as u16;
-                            i += 1;
-                        }
-                        row.extend(
-                            repeat(Cell::Number(Number {
-                                id: id_counter,
-                                value,
-                            }))
-                            .take(i - start),
-                        );
-                        id_counter += 1;
-                    }
-                    _ => {
-                        row.push(Cell::Symbol(c));
+#[derive(Debug)]
+struct Schematic {
+    cells: Vec<Cell>,
+    width: usize,
+    height: usize,
+}
+
+impl Schematic {
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// Returns the cells in the 3x3 square centered on `i`, clipped to the grid
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = &Cell> {
+        let x = i % self.width;
+        let y = i / self.width;
+
+        let x_start = x.saturating_sub(1);
+        let x_end = (x + 2).min(self.width);
+        let y_start = y.saturating_sub(1);
+        let y_end = (y + 2).min(self.height);
+
+        (y_start..y_end).flat_map(move |y| {
+            let start = self.index(x_start, y);
+            let end = self.index(x_end, y);
+            &self.cells[start..end]
+        })
+    }
+}
+
+fn parse_input(input: &str) -> Schematic {
+    // Pad ragged rows with empty cells so that every row has the same width
+    let width = input.lines().map(str::len).max().unwrap_or(0);
+    let mut cells = Vec::new();
+    for line in input.lines() {
+        let line = line.as_bytes(); // All characters in input are ASCII
+        let row_start = cells.len();
+
+        let mut i = 0;
+        while i < line.len() {
+            let c = line[i];
+            match c {
+                b'.' => {
+                    cells.push(Cell::Empty);
+                    i += 1;
+                }
+                b'0'..=b'9' => {
+                    let mut value = 0;
+                    let start = i;
+                    while i < line.len() && line[i].is_ascii_digit() {
+                        value = value * 10 + (line[i] - b'0') as u64;
                         i += 1;
                     }
+                    cells.extend(repeat_n(
+                        Cell::Number(Number {
+                            id: row_start + start,
+                            value,
+                        }),
+                        i - start,
+                    ));
+                }
+                _ => {
+                    cells.push(Cell::Symbol(c));
+                    i += 1;
                 }
             }
+        }
 
-            row
-        })
-        .collect();
+        cells.resize(row_start + width, Cell::Empty);
+    }
 
-    find_part_nums(&mut cells);
-    cells
+    let height = cells.len().checked_div(width).unwrap_or(0);
+    let mut schema = Schematic {
+        cells,
+        width,
+        height,
+    };
+    find_part_nums(&mut schema);
+    schema
 }
 
-fn find_part_nums(schema: &mut [Vec<Cell>]) {
-    let old = schema.to_owned();
-    for (i, row) in schema.iter_mut().enumerate() {
-        for (j, cell) in row.iter_mut().enumerate() {
-            if let Cell::Number(number) = cell {
-                let x_start = j.saturating_sub(1);
-                let x_end = (j + 2).min(old[i].len());
-                let y_start = i.saturating_sub(1);
-                let y_end = (i + 2).min(old.len());
-
-                'outer: for row in &old[y_start..y_end] {
-                    for c in &row[x_start..x_end] {
-                        if let Cell::Symbol(_) = c {
-                            *cell = Cell::PartNum(*number);
-                            break 'outer;
-                        }
-                    }
-                }
-            }
+fn find_part_nums(schema: &mut Schematic) {
+    let mut i = 0;
+    while i < schema.cells.len() {
+        let Cell::Number(number) = schema.cells[i] else {
+            i += 1;
+            continue;
+        };
+
+        let start = i;
+        while matches!(schema.cells.get(i), Some(Cell::Number(n)) if n.id == number.id) {
+            i += 1;
+        }
+        if (start..i).any(|j| schema.neighbours(j).any(|c| matches!(c, Cell::Symbol(_)))) {
+            schema.cells[start..i].fill(Cell::PartNum(number));
         }
     }
 }
 
 #[allow(dead_code)]
-pub fn part1() -> u32 {
+pub fn part1() -> u64 {
     let schema = parse_input(INPUT);
-
-    let mut max_id = 0;
     schema
+        .cells
         .iter()
-        .flatten()
-        .filter_map(|num| {
-            if let Cell::PartNum(number) = num {
-                if number.id >= max_id {
-                    max_id = number.id + 1;
-                    Some(number.value as u32)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+        .enumerate()
+        .filter_map(|(i, cell)| match cell {
+            // Only count each number once, at its first digit
+            Cell::PartNum(number) if number.id == i => Some(number.value),
+            _ => None,
         })
         .sum()
 }
 
 #[allow(dead_code)]
-pub fn part2() -> u32 {
+pub fn part2() -> u64 {
     let schema = parse_input(INPUT);
     schema
+        .cells
         .iter()
-        .flatten()
         .enumerate()
-        .filter_map(|(i, num)| {
-            if let Cell::Symbol(b'*') = num {
+        .filter_map(|(i, cell)| {
+            if let Cell::Symbol(b'*') = cell {
                 gear_ratio(&schema, i)
             } else {
                 None
@@ -126,39 +148,28 @@ pub fn part2() -> u32 {
         .sum()
 }
 
-fn gear_ratio(schema: &[Vec<Cell>], i: usize) -> Option<u32> {
-    let x = i % schema[0].len();
-    let y = i / schema[0].len();
-
-    let x_start = x.saturating_sub(1);
-    let x_end = (x + 2).min(schema[0].len());
-    let y_start = y.saturating_sub(1);
-    let y_end = (y + 2).min(schema.len());
-
-    let mut max_id = 0;
-    let mut first = None;
-    let mut second = None;
-    for row in &schema[y_start..y_end] {
-        for c in &row[x_start..x_end] {
-            if let Cell::PartNum(number) = c {
-                if number.id < max_id {
-                    continue;
-                }
+fn gear_ratio(schema: &Schematic, i: usize) -> Option<u64> {
+    let mut first: Option<Number> = None;
+    let mut second: Option<Number> = None;
+    for c in schema.neighbours(i) {
+        if let Cell::PartNum(number) = c {
+            if first.is_some_and(|n| n.id == number.id) || second.is_some_and(|n| n.id == number.id)
+            {
+                continue;
+            }
 
-                max_id = number.id + 1;
-                if first.is_none() {
-                    first = Some(number.value);
-                } else if second.is_none() {
-                    second = Some(number.value);
-                } else {
-                    return None;
-                }
+            if first.is_none() {
+                first = Some(*number);
+            } else if second.is_none() {
+                second = Some(*number);
+            } else {
+                return None;
             }
         }
     }
 
     if let (Some(first), Some(second)) = (first, second) {
-        Some(first as u32 * second as u32)
+        Some(first.value * second.value)
     } else {
         None
     }
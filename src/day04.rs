@@ -1,39 +1,143 @@
-use std::{collections::HashSet, iter::repeat};
+use std::collections::HashSet;
 
 const INPUT: &str = include_str!("day04.txt");
 
-fn parse_input() -> Vec<u8> {
-    INPUT
+/// How many copies of each following card a single copy of a card wins
+#[derive(Debug, Clone, Copy)]
+enum Scaling {
+    /// One copy per card
+    One,
+    /// As many copies as the card is worth in points
+    Points,
+}
+
+/// Rules for how winning cards hand out copies of later cards
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    scaling: Scaling,
+    /// Whether cards past the end of the table wrap around to the start.
+    /// Cards are still scratched in order, so copies won of earlier cards are
+    /// counted but not scratched again. A card never wins copies of itself, so
+    /// it wins at most one copy of every other card.
+    wrap: bool,
+    /// The maximum number of copies any one card can have
+    max_copies: Option<u128>,
+}
+
+impl Rules {
+    const PART2: Rules = Rules {
+        scaling: Scaling::One,
+        wrap: false,
+        max_copies: None,
+    };
+}
+
+fn points(matches: u32) -> u128 {
+    match matches {
+        0 => 0,
+        1..=128 => 1 << (matches - 1),
+        _ => u128::MAX,
+    }
+}
+
+/// Returns the digits of a number without leading zeros, so that equal
+/// numbers have equal digits. Panics if `num` isn't a number.
+fn digits(num: &str) -> &str {
+    assert!(
+        num.bytes().all(|c| c.is_ascii_digit()),
+        "not a number: {num:?}"
+    );
+    num.trim_start_matches('0')
+}
+
+/// Parses a number without allocating, or returns `None` if it doesn't fit
+fn parse_num(num: &str) -> Option<u32> {
+    digits(num).bytes().try_fold(0u32, |n, c| {
+        n.checked_mul(10)?.checked_add((c - b'0') as u32)
+    })
+}
+
+/// Returns the bitset of `nums`, or `None` if any number doesn't fit
+fn bitset(nums: &str) -> Option<u128> {
+    nums.split_ascii_whitespace().try_fold(0u128, |set, num| {
+        Some(set | 1u128.checked_shl(parse_num(num)?)?)
+    })
+}
+
+fn count_matches(wins: &str, nums: &str) -> u32 {
+    // Card numbers are small enough to fit in a bitset in practice
+    if let (Some(wins), Some(nums)) = (bitset(wins), bitset(nums)) {
+        return (wins & nums).count_ones();
+    }
+
+    // Compare digits instead, which works for numbers of any size
+    let drawn: HashSet<&str> = nums.split_ascii_whitespace().map(digits).collect();
+    wins.split_ascii_whitespace()
+        .filter(|num| drawn.contains(digits(num)))
+        .count() as u32
+}
+
+fn parse_input(input: &str) -> Vec<u32> {
+    input
         .lines()
         .map(|line| {
             let (_, line) = line.split_once(": ").unwrap();
             let (wins, nums) = line.split_once(" | ").unwrap();
-            let numbers: HashSet<u8> =
-                HashSet::from_iter(nums.split_whitespace().map(|s| s.parse().unwrap()));
-
-            wins.split_whitespace()
-                .map(|s| s.parse().unwrap())
-                .filter(|n| numbers.contains(n))
-                .count()
-                .try_into()
-                .unwrap()
+            count_matches(wins, nums)
         })
         .collect()
 }
 
+/// Returns the number of copies of each card, including the original, after
+/// all cards have been scratched
+fn copy_counts(matches: &[u32], rules: &Rules) -> Vec<u128> {
+    let len = matches.len();
+    let cap = rules.max_copies.unwrap_or(u128::MAX);
+    let mut counts = vec![1u128.min(cap); len];
+    for i in 0..len {
+        let won = match rules.scaling {
+            Scaling::One => counts[i],
+            Scaling::Points => counts[i].saturating_mul(points(matches[i])),
+        };
+
+        let max_won = if rules.wrap { len - 1 } else { len - i - 1 };
+        let end = i + 1 + (matches[i] as usize).min(max_won);
+        for j in i + 1..end {
+            let j = j % len;
+            counts[j] = counts[j].saturating_add(won).min(cap);
+        }
+    }
+    counts
+}
+
+/// Prints how many copies of each card there are once every card has been
+/// scratched
+pub fn print_copy_counts(input: &str, points: bool, wrap: bool, max_copies: Option<u128>) {
+    let rules = Rules {
+        scaling: if points {
+            Scaling::Points
+        } else {
+            Scaling::One
+        },
+        wrap,
+        max_copies,
+    };
+    let counts = copy_counts(&parse_input(input), &rules);
+    for (i, count) in counts.iter().enumerate() {
+        println!("Card {}: {count}", i + 1);
+    }
+    println!(
+        "Total: {}",
+        counts.iter().fold(0u128, |a, &b| a.saturating_add(b))
+    );
+}
+
 #[allow(dead_code)]
-pub fn part1() -> u32 {
-    parse_input().iter().map(|&m| 2u32.pow(m.into()) / 2).sum()
+pub fn part1() -> u128 {
+    parse_input(INPUT).into_iter().map(points).sum()
 }
 
 #[allow(dead_code)]
-pub fn part2() -> u32 {
-    let matches = parse_input();
-    let mut counts = repeat(1u32).take(matches.len()).collect::<Vec<_>>();
-    for i in 0..counts.len() - 1 {
-        for j in i + 1..i + 1 + matches[i] as usize {
-            counts[j] += counts[i];
-        }
-    }
-    counts.iter().sum()
+pub fn part2() -> u128 {
+    copy_counts(&parse_input(INPUT), &Rules::PART2).iter().sum()
 }
@@ -1,33 +1,119 @@
-use std::ops::Range;
+use std::{fmt::Display, ops::Range};
+
+use crate::interval_set::IntervalSet;
 
 const INPUT: &str = include_str!("day05.txt");
 
+/// A piecewise-linear map. After `normalise`, the pieces are sorted by `src`,
+/// don't overlap, and cover every `u64` except `u64::MAX`.
 #[derive(Debug)]
 struct Map(Vec<RangeMap>);
 
 impl Map {
+    fn normalise(mut self) -> Self {
+        self.0.sort_unstable_by_key(|map| map.src);
+
+        // Fill in gaps with identity maps
+        let mut pieces = Vec::with_capacity(self.0.len() * 2 + 1);
+        let mut end = 0;
+        for map in self.0 {
+            if map.src > end {
+                pieces.push(RangeMap::identity(end..map.src));
+            }
+            end = map.src + map.len;
+            pieces.push(map);
+        }
+        if end < u64::MAX {
+            pieces.push(RangeMap::identity(end..u64::MAX));
+        }
+
+        Map(pieces).coalesce()
+    }
+
+    /// Merges neighbouring pieces that continue each other
+    fn coalesce(self) -> Self {
+        let mut pieces: Vec<RangeMap> = Vec::with_capacity(self.0.len());
+        for map in self.0 {
+            match pieces.last_mut() {
+                Some(last)
+                    if last.src + last.len == map.src && last.dest + last.len == map.dest =>
+                {
+                    last.len += map.len;
+                }
+                _ => pieces.push(map),
+            }
+        }
+        Map(pieces)
+    }
+
+    /// Returns the map equivalent to applying `self` and then `next`. Both maps
+    /// must be normalised.
+    fn compose(&self, next: &Map) -> Map {
+        let mut pieces = Vec::with_capacity(self.0.len() + next.0.len());
+        for map in &self.0 {
+            let mut start = map.dest;
+            let end = map.dest + map.len;
+            let mut i = next.piece_index(start);
+            while start < end {
+                let next_map = &next.0[i];
+                let piece_end = end.min(next_map.src + next_map.len);
+                pieces.push(RangeMap {
+                    src: start - map.dest + map.src,
+                    dest: start - next_map.src + next_map.dest,
+                    len: piece_end - start,
+                });
+                start = piece_end;
+                i += 1;
+            }
+        }
+        Map(pieces).coalesce()
+    }
+
+    /// Returns the index of the piece containing `n`. The map must be normalised.
+    fn piece_index(&self, n: u64) -> usize {
+        self.0.partition_point(|map| map.src + map.len <= n)
+    }
+
     fn map_single(&self, n: u64) -> u64 {
-        self.0.iter().find_map(|map| map.map_single(n)).unwrap_or(n)
+        self.0
+            .get(self.piece_index(n))
+            .and_then(|map| map.map_single(n))
+            .unwrap_or(n)
+    }
+
+    /// Returns the inputs that map into `outputs`
+    fn preimage(&self, outputs: &IntervalSet<u64>) -> IntervalSet<u64> {
+        self.0.iter().fold(IntervalSet::new(), |inputs, map| {
+            inputs.union(&map.preimage(outputs))
+        })
+    }
+
+    fn map(&self, inputs: &IntervalSet<u64>) -> IntervalSet<u64> {
+        let mut outputs = IntervalSet::new();
+        let mut leftover = inputs.clone();
+        for map in &self.0 {
+            let src = map.src_range().into();
+            outputs = outputs.union(&inputs.intersection(&src).translate(map.src, map.dest));
+            leftover = leftover.difference(&src);
+        }
+        // Inputs not covered by any piece map to themselves
+        outputs.union(&leftover)
     }
+}
 
-    fn map(&self, ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
-        let mut mapped_ranges = Vec::new();
-        let leftover = self.0.iter().fold(ranges, |ranges, map| {
-            ranges
-                .iter()
-                .flat_map(|range| {
-                    let (mapped, left, right) = map.map(range.to_owned());
-                    if mapped.start < mapped.end {
-                        mapped_ranges.push(mapped);
-                    }
-                    [left, right]
-                })
-                // Filter out empty ranges
-                .filter(|r| r.start < r.end)
-                .collect()
-        });
-        mapped_ranges.extend(leftover);
-        mapped_ranges
+impl Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for map in &self.0 {
+            writeln!(
+                f,
+                "{}..{} -> {}..{}",
+                map.src,
+                map.src + map.len,
+                map.dest,
+                map.dest + map.len
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -39,6 +125,14 @@ struct RangeMap {
 }
 
 impl RangeMap {
+    fn identity(range: Range<u64>) -> Self {
+        RangeMap {
+            src: range.start,
+            dest: range.start,
+            len: range.end - range.start,
+        }
+    }
+
     fn map_single(&self, n: u64) -> Option<u64> {
         if n >= self.src && n < self.src + self.len {
             Some(n - self.src + self.dest)
@@ -47,43 +141,24 @@ impl RangeMap {
         }
     }
 
-    /// Returns (mapped, leftover1, leftover2)
-    fn map(&self, range: Range<u64>) -> (Range<u64>, Range<u64>, Range<u64>) {
-        let start = self.src;
-        let end = self.src + self.len;
-
-        if range.start >= end || range.end <= start {
-            // No overlap
-            (0..0, range, 0..0)
-        } else if range.start >= start && range.end <= end {
-            // Range complely inside map
-            let map_start = range.start - start + self.dest;
-            let map_end = range.end - start + self.dest;
-            (map_start..map_end, 0..0, 0..0)
-        } else if range.start <= start && range.end >= end {
-            // Map complely inside range
-            (
-                self.dest..self.dest + self.len,
-                range.start..start,
-                end..range.end,
-            )
-        } else {
-            // Partial overlap
-            let map_start = range.start.max(start) - start + self.dest;
-            let map_end = range.end.min(end) - start + self.dest;
-            let leftover_start = if range.start < start {
-                range.start
-            } else {
-                end
-            };
-            let leftover_end = if range.end > end { range.end } else { start };
-            (map_start..map_end, leftover_start..leftover_end, 0..0)
-        }
+    fn src_range(&self) -> Range<u64> {
+        self.src..self.src + self.len
+    }
+
+    fn dest_range(&self) -> Range<u64> {
+        self.dest..self.dest + self.len
+    }
+
+    /// Returns the inputs that map into `outputs`
+    fn preimage(&self, outputs: &IntervalSet<u64>) -> IntervalSet<u64> {
+        outputs
+            .intersection(&self.dest_range().into())
+            .translate(self.dest, self.src)
     }
 }
 
-fn parse_input() -> (Vec<u64>, Vec<Map>) {
-    let mut parts = INPUT.split("\n\n");
+fn parse_input(input: &str) -> (Vec<u64>, Vec<Map>) {
+    let mut parts = input.split("\n\n");
     let seeds = parts
         .next()
         .unwrap()
@@ -102,28 +177,71 @@ fn parse_input() -> (Vec<u64>, Vec<Map>) {
         }
     };
     let maps = parts
-        .map(|part| Map(part.lines().skip(1).map(str_to_range).collect()))
+        .map(|part| Map(part.lines().skip(1).map(str_to_range).collect()).normalise())
         .collect();
     (seeds, maps)
 }
 
+/// Returns the lowest output of `map` for any of `inputs`, by searching
+/// backwards from the lowest outputs
+fn min_output(map: &Map, inputs: &IntervalSet<u64>) -> Option<u64> {
+    let mut pieces: Vec<_> = map.0.iter().collect();
+    pieces.sort_unstable_by_key(|map| map.dest);
+
+    let mut min = None;
+    for piece in pieces {
+        if min.is_some_and(|min| min <= piece.dest) {
+            break;
+        }
+
+        let lowest = inputs.intersection(&piece.src_range().into()).min();
+        if let Some(lowest) = lowest {
+            let output = piece.map_single(lowest).unwrap();
+            min = Some(min.map_or(output, |min: u64| min.min(output)));
+        }
+    }
+    min
+}
+
+/// Composes all the maps into a single map from seed to location
+fn compose_all(maps: &[Map]) -> Map {
+    maps.iter()
+        .fold(Map(Vec::new()).normalise(), |acc, map| acc.compose(map))
+}
+
+/// Prints the single map from seed to location that all the maps compose to
+pub fn print_composed(input: &str) {
+    let (_, maps) = parse_input(input);
+    print!("{}", compose_all(&maps));
+}
+
 #[allow(dead_code)]
 pub fn part1() -> u64 {
-    let (seeds, maps) = parse_input();
+    let (seeds, maps) = parse_input(INPUT);
+    let seed_to_location = compose_all(&maps);
     seeds
         .iter()
-        .map(|&seed| maps.iter().fold(seed, |s, map| map.map_single(s)))
+        .map(|&seed| seed_to_location.map_single(seed))
         .min()
         .unwrap()
 }
 
 #[allow(dead_code)]
 pub fn part2() -> u64 {
-    let (seeds, maps) = parse_input();
-    let seeds = seeds.chunks(2).map(|nums| nums[0]..nums[0] + nums[1]);
-    seeds
-        .flat_map(|seed| maps.iter().fold(vec![seed], |s, map| map.map(s)))
-        .map(|r| r.start)
-        .min()
-        .unwrap()
+    let (seeds, maps) = parse_input(INPUT);
+    let seed_to_location = compose_all(&maps);
+    let seeds: IntervalSet<_> = seeds
+        .chunks(2)
+        .map(|nums| nums[0]..nums[0] + nums[1])
+        .collect();
+    let location = min_output(&seed_to_location, &seeds).unwrap();
+
+    // Check the answer against mapping forwards, and that some seed actually
+    // leads to this location
+    debug_assert_eq!(Some(location), seed_to_location.map(&seeds).min());
+    debug_assert!(!seed_to_location
+        .preimage(&(location..location + 1).into())
+        .intersection(&seeds)
+        .is_empty());
+    location
 }
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 const INPUT: &str = include_str!("day06.txt");
 
 // Let x be the time the button is held, t be the time of the race, and d be the distance traveled.
@@ -17,24 +19,131 @@ const INPUT: &str = include_str!("day06.txt");
 // r_2 = (t + sqrt(discriminant)) / 2
 // where discriminant = t^2 - 4d_record
 //
-// So we want the length of the range [ceil(r_1), floor(r_2)], right?
-// Well, r_1 and r_2 are not valid solutions as you have to go FURTHER than the record distance.
-// Hence,
-// start = floor(r_1) + 1
-// end = ceil(r_2) - 1
-// range = end - start + 1
-//       = ceil(r_2) - floor(r_1) - 1
-fn winnable_range(time: u64, record_dist: u64) -> u64 {
-    let discriminant = time * time - 4 * record_dist;
-    let discriminant_sqrt = (discriminant as f64).sqrt();
-    let root1 = ((time as f64) - discriminant_sqrt) / 2.0;
-    let root2 = ((time as f64) + discriminant_sqrt) / 2.0;
-    root2.ceil() as u64 - root1.floor() as u64 - 1
+// So we want the range [floor(r_1) + 1, ceil(r_2) - 1], as r_1 and r_2 are not valid solutions
+// as you have to go FURTHER than the record distance.
+//
+// To stay exact for large races, we work in u128 and use an integer sqrt to get just below r_1,
+// then step up to the first x that beats the record. By symmetry, the last one is t - start.
+/// Returns the inclusive range of hold times that beat the record, if any
+fn winnable_interval(time: u64, record_dist: u64) -> Option<(u64, u64)> {
+    let (t, d) = (time as u128, record_dist as u128);
+    let dist = |x: u128| (t - x) * x;
+
+    let discriminant = (t * t).checked_sub(4 * d)?;
+    let mut start = (t - isqrt(discriminant)) / 2;
+    while start <= t / 2 && dist(start) <= d {
+        start += 1;
+    }
+    if start > t / 2 {
+        return None;
+    }
+
+    Some((start as u64, (t - start) as u64))
 }
 
-#[allow(dead_code)]
-pub fn part1() -> u64 {
-    let mut lines = INPUT
+/// Returns the first value in `range` where `pred` is false, assuming that `pred` is true for
+/// some prefix of the range and false afterwards
+fn partition_point(range: Range<u64>, pred: impl Fn(u64) -> bool) -> u64 {
+    let (mut lo, mut hi) = (range.start, range.end);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// How a boat speeds up while its button is held
+#[derive(Debug, Clone, Copy)]
+struct Boat {
+    /// Speed gained per millisecond the button is held
+    accel: u64,
+    /// How long the button must be held before the boat starts gaining speed
+    delay: u64,
+    max_speed: Option<u64>,
+}
+
+impl Boat {
+    const TOY: Boat = Boat {
+        accel: 1,
+        delay: 0,
+        max_speed: None,
+    };
+
+    fn speed(&self, hold: u64) -> u128 {
+        let speed = self.accel as u128 * hold.saturating_sub(self.delay) as u128;
+        self.max_speed.map_or(speed, |max| speed.min(max as u128))
+    }
+
+    fn distance(&self, time: u64, hold: u64) -> u128 {
+        self.speed(hold).saturating_mul((time - hold) as u128)
+    }
+
+    /// Returns the hold time that goes the furthest
+    fn best_hold(&self, time: u64) -> u64 {
+        // Once the boat starts moving, the distance rises to a peak and then falls
+        let start = self.delay.min(time);
+        partition_point(start..time, |hold| {
+            self.distance(time, hold + 1) > self.distance(time, hold)
+        })
+    }
+
+    fn best_distance(&self, time: u64) -> u128 {
+        self.distance(time, self.best_hold(time))
+    }
+
+    /// Returns the inclusive range of hold times that beat the record, if any
+    fn winnable_interval(&self, time: u64, record_dist: u64) -> Option<(u64, u64)> {
+        if self.max_speed.is_none() && self.accel > 0 {
+            // Let y = x - delay. Then k * y(t - delay - y) > d_record is the same as
+            // y(t - delay - y) > floor(d_record / k), which is the quadratic case above
+            let (start, end) =
+                winnable_interval(time.checked_sub(self.delay)?, record_dist / self.accel)?;
+            return Some((start + self.delay, end + self.delay));
+        }
+
+        let record_dist = record_dist as u128;
+        let best = self.best_hold(time);
+        if self.distance(time, best) <= record_dist {
+            return None;
+        }
+
+        let start = partition_point(0..best, |hold| self.distance(time, hold) <= record_dist);
+        // The boat never goes anywhere if the button is held for the whole race, so this always
+        // finds the end
+        let end = partition_point(best..time, |hold| self.distance(time, hold) > record_dist) - 1;
+        Some((start, end))
+    }
+
+    fn winnable_range(&self, time: u64, record_dist: u64) -> u64 {
+        self.winnable_interval(time, record_dist)
+            .map_or(0, |(start, end)| end - start + 1)
+    }
+}
+
+/// Returns floor(sqrt(n))
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // Newton's method, starting from a power of 2 above the root
+    let mut x = 1 << ((128 - n.leading_zeros()).div_ceil(2));
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Returns the time and record distance of each race
+fn parse_races(input: &str) -> Vec<(u64, u64)> {
+    let mut lines = input
         .lines()
         .map(|line| line.split_once(':').unwrap().1.split_whitespace());
     lines
@@ -42,7 +151,36 @@ pub fn part1() -> u64 {
         .unwrap()
         .zip(lines.next().unwrap())
         .map(|(a, b)| (a.parse().unwrap(), b.parse().unwrap()))
-        .map(|(time, dist)| winnable_range(time, dist))
+        .collect()
+}
+
+/// Prints the best hold time, best distance and winning hold times of each race for a boat
+pub fn print_races(input: &str, accel: u64, delay: u64, max_speed: Option<u64>) {
+    let boat = Boat {
+        accel,
+        delay,
+        max_speed,
+    };
+    println!("Race | Time | Record | Best hold | Best distance | Winning holds");
+    for (i, (time, record)) in parse_races(input).into_iter().enumerate() {
+        let winning = match boat.winnable_interval(time, record) {
+            Some((start, end)) => format!("{start}..={end} ({})", end - start + 1),
+            None => "none".to_owned(),
+        };
+        println!(
+            "{:>4} | {time:>4} | {record:>6} | {:>9} | {:>13} | {winning}",
+            i + 1,
+            boat.best_hold(time),
+            boat.best_distance(time),
+        );
+    }
+}
+
+#[allow(dead_code)]
+pub fn part1() -> u64 {
+    parse_races(INPUT)
+        .into_iter()
+        .map(|(time, dist)| Boat::TOY.winnable_range(time, dist))
         .product()
 }
 
@@ -58,5 +196,5 @@ pub fn part2() -> u64 {
     });
     let time = lines.next().unwrap();
     let dist = lines.next().unwrap();
-    winnable_range(time, dist)
+    Boat::TOY.winnable_range(time, dist)
 }
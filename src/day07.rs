@@ -1,4 +1,4 @@
-use std::cmp::Ordering::{Equal, Greater, Less};
+use std::{cmp::Ordering, fmt::Display};
 
 const INPUT: &str = include_str!("day07.txt");
 
@@ -20,18 +20,21 @@ enum Card {
 }
 
 impl Card {
-    fn cmp1(&self, other: &Self) -> std::cmp::Ordering {
-        (*self as u8).cmp(&(*other as u8))
-    }
-
-    fn cmp2(&self, other: &Self) -> std::cmp::Ordering {
-        match (*self, *other) {
-            (Card::J, Card::J) => Equal,
-            (Card::J, _) => Less,
-            (_, Card::J) => Greater,
-            (a, b) => a.cmp1(&b),
-        }
-    }
+    const ALL: [Card; 13] = [
+        Card::Two,
+        Card::Three,
+        Card::Four,
+        Card::Five,
+        Card::Six,
+        Card::Seven,
+        Card::Eight,
+        Card::Nine,
+        Card::Ten,
+        Card::J,
+        Card::Q,
+        Card::K,
+        Card::A,
+    ];
 }
 
 impl TryFrom<char> for Card {
@@ -57,6 +60,12 @@ impl TryFrom<char> for Card {
     }
 }
 
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", "23456789TJQKA".as_bytes()[*self as usize] as char)
+    }
+}
+
 impl PartialEq for Card {
     fn eq(&self, other: &Self) -> bool {
         *self as u8 == *other as u8
@@ -96,136 +105,274 @@ impl Ord for HandKind {
     }
 }
 
+/// Count patterns for each kind of hand, strongest first
+const POKER_KINDS: &[(&[u8], HandKind)] = &[
+    (&[5], HandKind::FiveOfAKind),
+    (&[4], HandKind::FourOfAKind),
+    (&[3, 2], HandKind::FullHouse),
+    (&[3], HandKind::ThreeOfAKind),
+    (&[2, 2], HandKind::TwoPair),
+    (&[2], HandKind::OnePair),
+    (&[], HandKind::HighCard),
+];
+
+#[derive(Debug)]
+struct Ruleset {
+    /// Strength of each card, indexed by `Card as usize`
+    strength: [u8; 13],
+    /// Bitmask of cards that can stand in for any other card, indexed by `Card as usize`
+    wild: u16,
+    /// At most `MAX_HAND_SIZE`
+    hand_size: usize,
+    /// A hand is of the first kind whose pattern fits in its card counts, sorted from most to
+    /// least common
+    kinds: &'static [(&'static [u8], HandKind)],
+}
+
+impl Ruleset {
+    const PART1: Ruleset = Ruleset {
+        strength: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        wild: 0,
+        hand_size: 5,
+        kinds: POKER_KINDS,
+    };
+    const PART2: Ruleset = Ruleset {
+        strength: [1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 10, 11, 12],
+        wild: 1 << Card::J as u8,
+        hand_size: 5,
+        kinds: POKER_KINDS,
+    };
+
+    fn is_wild(&self, card: Card) -> bool {
+        self.wild & (1 << card as u8) != 0
+    }
+
+    fn strength(&self, card: Card) -> u8 {
+        self.strength[card as usize]
+    }
+}
+
+/// The most cards a hand can have, so that sort keys fit in a u64
+const MAX_HAND_SIZE: usize = 15;
+
 #[derive(Debug, Clone, Copy)]
-struct Hand([Card; 5]);
+struct Hand<'a> {
+    /// Only the first `len` cards are in the hand
+    cards: [Card; MAX_HAND_SIZE],
+    len: u8,
+    rules: &'a Ruleset,
+    /// Computed once up front, rather than reclassifying the hand on every comparison
+    key: u64,
+}
+
+impl<'a> Hand<'a> {
+    fn new(cards: &[Card], rules: &'a Ruleset) -> Self {
+        assert!(cards.len() <= MAX_HAND_SIZE);
+        let mut hand = Hand {
+            cards: [Card::Two; MAX_HAND_SIZE],
+            len: cards.len() as u8,
+            rules,
+            key: 0,
+        };
+        hand.cards[..cards.len()].copy_from_slice(cards);
+        hand.key = hand.sort_key();
+        hand
+    }
 
-impl Hand {
-    fn kind1(&self) -> HandKind {
+    fn cards(&self) -> &[Card] {
+        &self.cards[..self.len as usize]
+    }
+
+    fn kind(&self) -> HandKind {
         let mut counts = [0u8; 13];
-        for &card in self.0.iter() {
-            counts[card as usize] += 1;
+        let mut wild_count = 0;
+        for &card in self.cards() {
+            if self.rules.is_wild(card) {
+                wild_count += 1;
+            } else {
+                counts[card as usize] += 1;
+            }
         }
 
-        match counts.iter().max().unwrap() {
-            5 => HandKind::FiveOfAKind,
-            4 => HandKind::FourOfAKind,
-            3 => {
-                if counts.iter().any(|&count| count == 2) {
-                    HandKind::FullHouse
-                } else {
-                    HandKind::ThreeOfAKind
-                }
-            }
-            2 => {
-                if counts.iter().filter(|&&count| count == 2).count() == 2 {
-                    HandKind::TwoPair
-                } else {
-                    HandKind::OnePair
-                }
-            }
-            1 => HandKind::HighCard,
-            _ => unreachable!(),
+        // Only sort the counts of cards that are actually in the hand
+        let mut groups = [0u8; 16];
+        let mut group_count = 0;
+        for count in counts.into_iter().filter(|&count| count > 0) {
+            groups[group_count] = count;
+            group_count += 1;
         }
+        let groups = &mut groups[..group_count.max(1)];
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        // Wild cards are always best used to copy the most common card
+        groups[0] += wild_count;
+
+        self.rules
+            .kinds
+            .iter()
+            .find(|(pattern, _)| {
+                pattern.len() <= groups.len()
+                    && pattern.iter().zip(groups.iter()).all(|(&p, &c)| c >= p)
+            })
+            .unwrap()
+            .1
     }
 
-    fn kind2(&self) -> HandKind {
+    /// Packs the kind followed by the strength of each card into 4 bits each, so that comparing
+    /// keys is the same as comparing hands
+    fn sort_key(&self) -> u64 {
+        self.cards().iter().fold(self.kind() as u64, |key, &card| {
+            key << 4 | self.rules.strength(card) as u64
+        })
+    }
+
+    /// Returns the card that wild cards stand in for. This is the most common card, or the
+    /// strongest card if there is nothing to copy.
+    fn wild_target(&self) -> Card {
         let mut counts = [0u8; 13];
-        for &card in self.0.iter() {
-            counts[card as usize] += 1;
-        }
-        let joker_count = counts[Card::J as usize];
-        counts[Card::J as usize] = 0;
-
-        match counts.iter().max().unwrap() + joker_count {
-            5 => HandKind::FiveOfAKind,
-            4 => HandKind::FourOfAKind,
-            3 => {
-                // If there is a joker, there may be 2 pairs
-                if counts.iter().filter(|&&count| count >= 2).count() == 2 {
-                    HandKind::FullHouse
-                } else {
-                    HandKind::ThreeOfAKind
-                }
+        for &card in self.cards() {
+            if !self.rules.is_wild(card) {
+                counts[card as usize] += 1;
             }
-            2 => {
-                // It is impossible to get two pair with a joker, so 2 pairs are needed
-                if counts.iter().filter(|&&count| count == 2).count() == 2 {
-                    HandKind::TwoPair
+        }
+
+        Card::ALL
+            .into_iter()
+            .filter(|&card| !self.rules.is_wild(card))
+            .max_by_key(|&card| (counts[card as usize], self.rules.strength(card)))
+            .unwrap_or(Card::A)
+    }
+
+    /// Returns the cards with each wild card replaced by the card it stands in for
+    fn as_played(&self) -> Vec<Card> {
+        let target = self.wild_target();
+        self.cards()
+            .iter()
+            .map(|&card| {
+                if self.rules.is_wild(card) {
+                    target
                 } else {
-                    HandKind::OnePair
+                    card
                 }
-            }
-            1 => HandKind::HighCard,
-            _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Returns the position and cards of the first card that breaks the tie between two hands of
+    /// the same kind. Returns `None` if the hands are different kinds or have equal cards.
+    fn tie_break(&self, other: &Self) -> Option<(usize, Card, Card)> {
+        if self.kind() != other.kind() {
+            return None;
+        }
+        self.cards()
+            .iter()
+            .zip(other.cards())
+            .enumerate()
+            .find(|(_, (&a, &b))| self.rules.strength(a) != other.rules.strength(b))
+            .map(|(i, (&a, &b))| (i, a, b))
+    }
+}
+
+impl Display for Hand<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for card in self.cards() {
+            write!(f, "{card}")?;
         }
+        Ok(())
     }
 }
 
-fn parse_input() -> Vec<(Hand, u16)> {
-    INPUT
+impl PartialEq for Hand<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Hand<'_> {}
+
+impl PartialOrd for Hand<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Hand<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        debug_assert!(std::ptr::eq(self.rules, other.rules));
+        self.key.cmp(&other.key)
+    }
+}
+
+fn parse_input<'a>(input: &str, rules: &'a Ruleset) -> Vec<(Hand<'a>, u16)> {
+    input
         .lines()
         .map(|line| {
             let (hand, bet) = line.split_once(' ').unwrap();
-            let mut cards = hand.chars();
-
-            let mut hand = Hand([Card::A; 5]);
-            for card in hand.0.iter_mut() {
-                *card = cards.next().unwrap().try_into().unwrap();
+            assert_eq!(hand.len(), rules.hand_size);
+            let mut cards = [Card::Two; MAX_HAND_SIZE];
+            for (card, c) in cards.iter_mut().zip(hand.bytes()) {
+                *card = (c as char).try_into().unwrap();
             }
+
             let bet = bet.parse().unwrap();
-            (hand, bet)
+            (Hand::new(&cards[..hand.len()], rules), bet)
         })
         .collect()
 }
 
-#[allow(dead_code)]
-pub fn part1() -> u32 {
-    let mut input = parse_input();
-    input.sort_by(|(a, _), (b, _)| cmp_hands1(a, b));
-    input
-        .iter()
-        .enumerate()
-        .map(|(i, (_, bet))| (i + 1) as u32 * *bet as u32)
-        .sum()
+/// Returns the hands and bets from weakest to strongest
+fn ranking<'a>(input: &str, rules: &'a Ruleset) -> Vec<(Hand<'a>, u16)> {
+    let mut hands = parse_input(input, rules);
+    hands.sort_unstable_by_key(|&(hand, _)| hand.key);
+    hands
 }
 
-fn cmp_hands1(a: &Hand, b: &Hand) -> std::cmp::Ordering {
-    match a.kind1().cmp(&b.kind1()) {
-        Less => Less,
-        Greater => Greater,
-        Equal => {
-            for (a, b) in a.0.iter().zip(b.0.iter()) {
-                if a != b {
-                    return a.cmp1(b);
-                }
-            }
-            Equal
-        }
+/// Prints every hand from weakest to strongest, along with why it beats the hand before it
+pub fn print_ranking(input: &str, jokers: bool) {
+    let rules = if jokers {
+        &Ruleset::PART2
+    } else {
+        &Ruleset::PART1
+    };
+
+    println!("Rank | Hand  | Kind         | Played | Bid  | Winnings | Beats previous by");
+    let hands = ranking(input, rules);
+    let mut total = 0;
+    for (i, (hand, bet)) in hands.iter().enumerate() {
+        let winnings = (i + 1) as u64 * *bet as u64;
+        total += winnings;
+
+        let reason = match i.checked_sub(1).map(|j| &hands[j].0) {
+            None => String::new(),
+            Some(prev) => match hand.tie_break(prev) {
+                Some((pos, a, b)) => format!("card {} ({a} > {b})", pos + 1),
+                None if hand.kind() == prev.kind() => "nothing (tie)".to_owned(),
+                None => format!("kind ({:?} > {:?})", hand.kind(), prev.kind()),
+            },
+        };
+        let played: String = hand.as_played().iter().map(Card::to_string).collect();
+        println!(
+            "{:>4} | {hand} | {:<12} | {played:<6} | {bet:>4} | {winnings:>8} | {reason}",
+            i + 1,
+            format!("{:?}", hand.kind()),
+        );
     }
+    println!("Total winnings: {total}");
 }
 
-#[allow(dead_code)]
-pub fn part2() -> u32 {
-    let mut input = parse_input();
-    input.sort_by(|(a, _), (b, _)| cmp_hands2(a, b));
-    input
+fn total_winnings(rules: &Ruleset) -> u64 {
+    ranking(INPUT, rules)
         .iter()
         .enumerate()
-        .map(|(i, (_, bet))| (i + 1) as u32 * *bet as u32)
+        .map(|(i, (_, bet))| (i + 1) as u64 * *bet as u64)
         .sum()
 }
 
-fn cmp_hands2(a: &Hand, b: &Hand) -> std::cmp::Ordering {
-    match a.kind2().cmp(&b.kind2()) {
-        Less => Less,
-        Greater => Greater,
-        Equal => {
-            for (a, b) in a.0.iter().zip(b.0.iter()) {
-                if a != b {
-                    return a.cmp2(b);
-                }
-            }
-            Equal
-        }
-    }
+#[allow(dead_code)]
+pub fn part1() -> u64 {
+    total_winnings(&Ruleset::PART1)
+}
+
+#[allow(dead_code)]
+pub fn part2() -> u64 {
+    total_winnings(&Ruleset::PART2)
 }
@@ -2,102 +2,346 @@ use std::{collections::HashMap, iter::repeat};
 
 const INPUT: &str = include_str!("day08.txt");
 
-fn name_to_id(name: &[u8]) -> u16 {
-    let mut id: u16 = 0;
-    id += (name[0] - b'A') as u16;
-    id *= 26;
-    id += (name[1] - b'A') as u16;
-    id *= 26;
-    id += (name[2] - b'A') as u16;
-    id
-}
-
-fn parse_input() -> (&'static str, HashMap<u16, (u16, u16)>) {
-    let (instructions, graph) = INPUT.split_once("\n\n").unwrap();
-    let graph = graph
-        .lines()
-        .map(|line| {
-            let node = name_to_id(&line.as_bytes()[0..3]);
-            let left = name_to_id(&line.as_bytes()[7..10]);
-            let right = name_to_id(&line.as_bytes()[12..15]);
-            (node, (left, right))
-        })
-        .collect();
-    (instructions, graph)
+/// The left/right network, with node names interned to dense indices
+#[derive(Debug)]
+struct Network<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, u32>,
+    /// Left and right neighbours of each node
+    edges: Vec<(u32, u32)>,
 }
 
-fn next_node(instruction: char, current: u16, graph: &HashMap<u16, (u16, u16)>) -> u16 {
-    let node = graph.get(&current).unwrap();
-    match instruction {
-        'L' => node.0,
-        'R' => node.1,
-        _ => unreachable!(),
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> Self {
+        let mut ids = HashMap::new();
+        let mut names = Vec::new();
+        let mut intern = |name: &'a str| -> u32 {
+            *ids.entry(name).or_insert_with(|| {
+                names.push(name);
+                names.len() as u32 - 1
+            })
+        };
+
+        let mut edges = Vec::new();
+        for line in input.lines() {
+            let (node, rest) = line.split_once(" = ").unwrap();
+            let (left, right) = rest
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split_once(", ")
+                .unwrap();
+            let (node, left, right) = (intern(node), intern(left), intern(right));
+
+            // Nodes can be named before their own line
+            let len = edges.len().max(left.max(right).max(node) as usize + 1);
+            edges.resize(len, (u32::MAX, u32::MAX));
+            edges[node as usize] = (left, right);
+        }
+        Network { names, ids, edges }
+    }
+
+    fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    /// Returns the ids of nodes whose names satisfy `pred`
+    fn find(&self, pred: impl Fn(&str) -> bool) -> Vec<u32> {
+        (0..self.names.len() as u32)
+            .filter(|&i| pred(self.names[i as usize]))
+            .collect()
+    }
+
+    /// Returns whether each node's name satisfies `pred`
+    fn mark(&self, pred: impl Fn(&str) -> bool) -> Vec<bool> {
+        self.names.iter().map(|name| pred(name)).collect()
+    }
+
+    fn next(&self, instruction: char, current: u32) -> u32 {
+        let node = self.edges[current as usize];
+        match instruction {
+            'L' => node.0,
+            'R' => node.1,
+            _ => unreachable!(),
+        }
     }
 }
 
+fn parse_input(input: &str) -> (&str, Network<'_>) {
+    let (instructions, network) = input.split_once("\n\n").unwrap();
+    (instructions, Network::parse(network))
+}
+
 #[allow(dead_code)]
 pub fn part1() -> u32 {
-    const START: u16 = 0;
-    const END: u16 = 26 * 26 * 26 - 1;
-
-    let (instructions, graph) = parse_input();
+    let (instructions, network) = parse_input(INPUT);
+    let end = network.id("ZZZ").unwrap();
     // Repeat infinitely
     let instructions = repeat(instructions.chars()).flatten();
 
     let mut steps = 0;
-    let mut current = START;
+    let mut current = network.id("AAA").unwrap();
     for instruct in instructions {
-        if current == END {
+        if current == end {
             break;
         }
-        current = next_node(instruct, current, &graph);
+        current = network.next(instruct, current);
         steps += 1;
     }
     steps
 }
 
-#[allow(dead_code)]
-pub fn part2() -> u64 {
-    let (instructions, graph) = parse_input();
-    // Repeat infinitely
-    let instructions = repeat(instructions.chars()).flatten();
+/// The steps at which a ghost is on an end node
+#[derive(Debug)]
+struct GhostHits {
+    /// Hits before the ghost enters its cycle
+    prefix: Vec<u128>,
+    /// Hits within the first pass of the cycle. Every later hit is one of these plus some
+    /// multiple of `cycle_len`.
+    cycle: Vec<u128>,
+    cycle_start: u128,
+    cycle_len: u128,
+}
+
+impl GhostHits {
+    fn contains(&self, step: u128) -> bool {
+        if step < self.cycle_start {
+            self.prefix.contains(&step)
+        } else {
+            let offset = (step - self.cycle_start) % self.cycle_len;
+            self.cycle.contains(&(self.cycle_start + offset))
+        }
+    }
+}
+
+fn walk_ghost(instructions: &str, network: &Network, is_end: &[bool], start: u32) -> GhostHits {
+    // The ghost's state is its node and position in the instructions. It's enough to only
+    // remember states at the start of the instructions to find a cycle.
+    let mut seen = vec![None; network.edges.len()];
+    let mut hits = Vec::new();
+    let mut current = start;
+    let mut step = 0;
+    let (cycle_start, cycle_len) = loop {
+        if let Some(first) = seen[current as usize] {
+            break (first, step - first);
+        }
+        seen[current as usize] = Some(step);
 
-    graph
-        .keys()
-        .filter(|&k| ends_with_a(k))
-        .map(|&node| {
-            let mut current = node;
-            let mut steps = 0;
-            let instructions = instructions.clone();
-            for instruct in instructions {
-                if ends_with_z(&current) {
-                    break;
-                }
-                current = next_node(instruct, current, &graph);
-                steps += 1;
+        for instruct in instructions.chars() {
+            if is_end[current as usize] {
+                hits.push(step);
+            }
+            current = network.next(instruct, current);
+            step += 1;
+        }
+    };
+
+    let (prefix, cycle) = hits.into_iter().partition(|&hit| hit < cycle_start);
+    GhostHits {
+        prefix,
+        cycle,
+        cycle_start,
+        cycle_len,
+    }
+}
+
+/// Returns the first step at which every ghost is on an end node, if there is one
+fn solve_ghosts(ghosts: &[GhostHits]) -> Option<u128> {
+    // Any step before some ghost's cycle must be one of that ghost's prefix hits
+    let early = ghosts
+        .iter()
+        .flat_map(|ghost| &ghost.prefix)
+        .filter(|&&step| ghosts.iter().all(|ghost| ghost.contains(step)))
+        .min()
+        .copied();
+
+    // Otherwise, every ghost is in its cycle. Combine the cycles one at a time, keeping every
+    // residue that works for all the ghosts so far.
+    let mut residues = vec![0];
+    let mut modulus = 1;
+    for ghost in ghosts {
+        let mut next: Vec<_> = residues
+            .iter()
+            .flat_map(|&r| {
+                ghost
+                    .cycle
+                    .iter()
+                    .filter_map(move |&hit| crt(r, modulus, hit, ghost.cycle_len))
+            })
+            .collect();
+        next.sort_unstable();
+        next.dedup();
+        residues = next;
+        modulus = modulus / gcd(modulus, ghost.cycle_len) * ghost.cycle_len;
+    }
+    let min_step = ghosts.iter().map(|ghost| ghost.cycle_start).max()?;
+    let late = residues
+        .into_iter()
+        .map(|r| {
+            if r >= min_step {
+                r
+            } else {
+                r + (min_step - r).div_ceil(modulus) * modulus
             }
-            // The XXZ nodes seem to always go back to the original XXA node,
-            // so loop_size = initial_steps_to_XXZ
-            steps
-        })
-        .reduce(|acc, x| {
-            // The closed loops also means that the answer is just the LCM of
-            // the loop sizes
-            let gcd = gcd(acc, x);
-            acc / gcd * x
         })
-        .unwrap()
+        .min();
+
+    match (early, late) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
 }
 
-fn ends_with_a(id: &u16) -> bool {
-    id % 26 == 0
+/// Returns the smallest x >= 0 with x = a (mod m) and x = b (mod n), modulo lcm(m, n), if
+/// there is one
+fn crt(a: u128, m: u128, b: u128, n: u128) -> Option<u128> {
+    let g = gcd(m, n);
+    let (a, b) = (a % m, b % n);
+    if a.abs_diff(b) % g != 0 {
+        return None;
+    }
+
+    // Solve m * k = b - a (mod n)
+    let n_g = n / g;
+    let diff = (b + n - a % n) % n / g;
+    let k = mul_mod(diff, mod_inverse(m / g % n_g, n_g), n_g);
+    Some(a + m * k)
+}
+
+fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    // Double-and-add, since a * b can overflow
+    let (mut a, mut b, mut result) = (a % m, b, 0);
+    while b > 0 {
+        if b & 1 == 1 {
+            result = (result + a) % m;
+        }
+        a = (a + a) % m;
+        b >>= 1;
+    }
+    result
+}
+
+/// Returns the inverse of `a` modulo `m`. `a` and `m` must be coprime.
+fn mod_inverse(a: u128, m: u128) -> u128 {
+    if m == 1 {
+        return 0;
+    }
+
+    // Extended Euclidean algorithm
+    let (mut old_r, mut r) = (a as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    old_s.rem_euclid(m as i128) as u128
+}
+
+/// Returns the first step at which ghosts starting from every start node are all on end nodes, if
+/// there is one
+fn walk_ghosts(
+    instructions: &str,
+    network: &Network,
+    is_start: impl Fn(&str) -> bool,
+    is_end: impl Fn(&str) -> bool,
+) -> Option<u128> {
+    let is_end = network.mark(is_end);
+    let ghosts: Vec<_> = network
+        .find(is_start)
+        .into_iter()
+        .map(|start| walk_ghost(instructions, network, &is_end, start))
+        .collect();
+    solve_ghosts(&ghosts)
+}
+
+/// Returns the nodes a ghost visits once it is in its cycle
+fn cycle_nodes(instructions: &str, network: &Network, is_end: &[bool], start: u32) -> Vec<u32> {
+    let ghost = walk_ghost(instructions, network, is_end, start);
+    let mut instructions = repeat(instructions.chars()).flatten();
+    let mut current = start;
+    for _ in 0..ghost.cycle_start {
+        current = network.next(instructions.next().unwrap(), current);
+    }
+
+    let mut nodes = Vec::new();
+    for _ in 0..ghost.cycle_len {
+        nodes.push(current);
+        current = network.next(instructions.next().unwrap(), current);
+    }
+    nodes
 }
 
-fn ends_with_z(id: &u16) -> bool {
-    id % 26 == 25
+/// Whether a ghost starts on a node in part 2
+pub fn is_ghost_start(name: &str) -> bool {
+    name.ends_with('A')
+}
+
+/// Whether a node is a ghost's end in part 2
+pub fn is_ghost_end(name: &str) -> bool {
+    name.ends_with('Z')
+}
+
+/// Returns the network in Graphviz DOT format, with start nodes in green and end nodes in red. If
+/// `cycles_only` is set, only the nodes on some ghost's cycle are kept.
+pub fn to_dot(
+    input: &str,
+    is_start: impl Fn(&str) -> bool,
+    is_end: impl Fn(&str) -> bool,
+    cycles_only: bool,
+) -> String {
+    let (instructions, network) = parse_input(input);
+    let starts = network.find(&is_start);
+    let is_start = network.mark(is_start);
+    let is_end = network.mark(is_end);
+
+    let mut keep = vec![!cycles_only; network.names.len()];
+    if cycles_only {
+        for start in starts {
+            for node in cycle_nodes(instructions, &network, &is_end, start) {
+                keep[node as usize] = true;
+            }
+        }
+    }
+
+    let quote = |id: u32| format!("\"{}\"", network.names[id as usize].replace('"', "\\\""));
+    let mut dot = String::from("digraph network {\n    node [shape=circle];\n");
+    for id in (0..network.names.len() as u32).filter(|&id| keep[id as usize]) {
+        let colour = match (is_start[id as usize], is_end[id as usize]) {
+            (true, _) => "palegreen",
+            (_, true) => "salmon",
+            _ => continue,
+        };
+        dot += &format!("    {} [style=filled, fillcolor={colour}];\n", quote(id));
+    }
+    for (id, &(left, right)) in network.edges.iter().enumerate() {
+        let id = id as u32;
+        if !keep[id as usize] {
+            continue;
+        }
+
+        let edges: &[_] = if left == right {
+            &[(left, "LR")]
+        } else {
+            &[(left, "L"), (right, "R")]
+        };
+        for &(to, label) in edges {
+            if keep.get(to as usize) == Some(&true) {
+                dot += &format!("    {} -> {} [label={label}];\n", quote(id), quote(to));
+            }
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+#[allow(dead_code)]
+pub fn part2() -> u128 {
+    let (instructions, network) = parse_input(INPUT);
+    walk_ghosts(instructions, &network, is_ghost_start, is_ghost_end)
+        .expect("ghosts are never all on Z nodes at once")
 }
 
-fn gcd(a: u64, b: u64) -> u64 {
+fn gcd(a: u128, b: u128) -> u128 {
     if b == 0 {
         return a;
     }
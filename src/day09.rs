@@ -1,7 +1,82 @@
 const INPUT: &str = include_str!("day09.txt");
 
-fn parse_input() -> Vec<Vec<i32>> {
-    INPUT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FitError {
+    /// No row of the difference table with at least 2 values is constant, in a history long
+    /// enough to have one
+    NotPolynomial,
+    Overflow,
+}
+
+/// A polynomial in Newton's forward difference form, so that
+/// f(x) = sum_k diffs[k] * C(x, k), where x = 0 is the first reading
+#[derive(Debug)]
+struct Polynomial {
+    diffs: Vec<i128>,
+}
+
+impl Polynomial {
+    fn fit(history: &[i64]) -> Result<Self, FitError> {
+        let mut diffs: Vec<i128> = history.iter().map(|&x| x as i128).collect();
+        let n = diffs.len();
+
+        // Build the difference table in place. After level k, diffs[..k] holds the first
+        // difference of each earlier level and diffs[k..] holds row k. Histories of 1 or 2
+        // readings are too short to prove anything, so their last row counts as constant.
+        for k in 0..n {
+            let row = &diffs[k..];
+            if row.iter().all(|&x| x == row[0]) && (row.len() > 1 || n <= 2) {
+                diffs.truncate(k + 1);
+                return Ok(Polynomial { diffs });
+            }
+
+            for i in (k + 1..n).rev() {
+                diffs[i] = diffs[i]
+                    .checked_sub(diffs[i - 1])
+                    .ok_or(FitError::Overflow)?;
+            }
+        }
+        Err(FitError::NotPolynomial)
+    }
+
+    fn degree(&self) -> usize {
+        self.diffs.len() - 1
+    }
+
+    /// Returns f(x). x can be before the first reading or past the last one.
+    fn at(&self, x: i128) -> Result<i128, FitError> {
+        let mut value: i128 = 0;
+        // C(x, k), computed incrementally as C(x, k - 1) * (x - k + 1) / k
+        let mut binomial: i128 = 1;
+        for (k, &diff) in self.diffs.iter().enumerate() {
+            if k > 0 {
+                binomial = binomial
+                    .checked_mul(x - k as i128 + 1)
+                    .ok_or(FitError::Overflow)?
+                    / k as i128;
+            }
+            value = diff
+                .checked_mul(binomial)
+                .and_then(|term| value.checked_add(term))
+                .ok_or(FitError::Overflow)?;
+        }
+        Ok(value)
+    }
+}
+
+/// Returns the reading `steps` after the last one, or before the first one if `steps` is negative
+fn extrapolate(history: &[i64], steps: i64) -> Result<i128, FitError> {
+    let poly = Polynomial::fit(history)?;
+    let x = if steps >= 0 {
+        history.len() as i128 - 1 + steps as i128
+    } else {
+        steps as i128
+    };
+    poly.at(x)
+}
+
+fn parse_input(input: &str) -> Vec<Vec<i64>> {
+    input
         .lines()
         .map(|line| {
             line.split_whitespace()
@@ -11,36 +86,42 @@ fn parse_input() -> Vec<Vec<i32>> {
         .collect()
 }
 
-#[allow(dead_code)]
-pub fn part1() -> i32 {
-    parse_input().iter().map(|row| next_reading(row)).sum()
-}
-
-fn next_reading(history: &[i32]) -> i32 {
-    if history.iter().all(|&x| x == history[0]) {
-        return history[0];
+/// Prints the degree of each history along with the readings before and after it
+pub fn print_extrapolations(input: &str) {
+    for (i, history) in parse_input(input).iter().enumerate() {
+        let poly = match Polynomial::fit(history) {
+            Ok(poly) => poly,
+            Err(err) => {
+                println!("History {}: {err:?}", i + 1);
+                continue;
+            }
+        };
+        let show = |value: Result<i128, FitError>| match value {
+            Ok(value) => value.to_string(),
+            Err(err) => format!("{err:?}"),
+        };
+        println!(
+            "History {}: degree {}, previous {}, next {}",
+            i + 1,
+            poly.degree(),
+            show(extrapolate(history, -1)),
+            show(extrapolate(history, 1)),
+        );
     }
-
-    let diffs = history
-        .windows(2)
-        .map(|window| window[1] - window[0])
-        .collect::<Vec<_>>();
-    history[history.len() - 1] + next_reading(&diffs)
 }
 
 #[allow(dead_code)]
-pub fn part2() -> i32 {
-    parse_input().iter().map(|row| prev_reading(row)).sum()
+pub fn part1() -> i128 {
+    parse_input(INPUT)
+        .iter()
+        .map(|row| extrapolate(row, 1).unwrap())
+        .sum()
 }
 
-fn prev_reading(history: &[i32]) -> i32 {
-    if history.iter().all(|&x| x == history[0]) {
-        return history[0];
-    }
-
-    let diffs = history
-        .windows(2)
-        .map(|window| window[1] - window[0])
-        .collect::<Vec<_>>();
-    history[0] - prev_reading(&diffs)
+#[allow(dead_code)]
+pub fn part2() -> i128 {
+    parse_input(INPUT)
+        .iter()
+        .map(|row| extrapolate(row, -1).unwrap())
+        .sum()
 }
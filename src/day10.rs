@@ -1,143 +1,204 @@
-use std::iter::repeat;
-
 const INPUT: &str = include_str!("day10.txt");
 
-#[allow(dead_code)]
-pub fn part1() -> u32 {
-    let grid = INPUT.lines().map(|s| s.as_bytes()).collect::<Vec<_>>();
+const NORTH: u8 = 1;
+const EAST: u8 = 2;
+const SOUTH: u8 = 4;
+const WEST: u8 = 8;
 
-    let (start_x, start_y) = find_start(&grid);
-    let (mut x, mut y) = next_pipe(&grid, start_x, start_y, start_x, start_y);
-    let mut steps = 1;
-    let mut prev_x = start_x;
-    let mut prev_y = start_y;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MazeError {
+    NoStart,
+    /// None of the pipes next to S lead back to it
+    NoLoop,
+}
 
-    while start_x != x || start_y != y {
-        let (next_x, next_y) = next_pipe(&grid, x, y, prev_x, prev_y);
-        prev_x = x;
-        prev_y = y;
-        x = next_x;
-        y = next_y;
-        steps += 1;
+/// Returns the directions a tile connects to
+fn connections(tile: u8) -> u8 {
+    match tile {
+        b'|' => NORTH | SOUTH,
+        b'-' => EAST | WEST,
+        b'L' => NORTH | EAST,
+        b'J' => NORTH | WEST,
+        b'7' => SOUTH | WEST,
+        b'F' => SOUTH | EAST,
+        _ => 0,
     }
+}
+
+/// Returns the pipe that connects in the given directions
+fn pipe_with(connections: u8) -> u8 {
+    match connections {
+        c if c == NORTH | SOUTH => b'|',
+        c if c == EAST | WEST => b'-',
+        c if c == NORTH | EAST => b'L',
+        c if c == NORTH | WEST => b'J',
+        c if c == SOUTH | WEST => b'7',
+        c if c == SOUTH | EAST => b'F',
+        _ => unreachable!(),
+    }
+}
+
+fn opposite(dir: u8) -> u8 {
+    (dir << 2 | dir >> 2) & 0xF
+}
+
+/// Returns the tile one step in `dir`, if it is on the grid
+fn step(grid: &[&[u8]], x: usize, y: usize, dir: u8) -> Option<(usize, usize)> {
+    let (x, y) = match dir {
+        NORTH => (x, y.checked_sub(1)?),
+        EAST => (x + 1, y),
+        SOUTH => (x, y + 1),
+        WEST => (x.checked_sub(1)?, y),
+        _ => unreachable!(),
+    };
+    grid.get(y)?.get(x)?;
+    Some((x, y))
+}
 
-    steps / 2
+/// The main loop through S
+#[derive(Debug)]
+struct PipeLoop {
+    /// The tiles of the loop in order, starting from S
+    tiles: Vec<(usize, usize)>,
+    /// The pipe hidden under S
+    start_pipe: u8,
 }
 
-fn find_start(grid: &[&[u8]]) -> (usize, usize) {
+impl PipeLoop {
+    fn trace(grid: &[&[u8]]) -> Result<Self, MazeError> {
+        let start = find_start(grid).ok_or(MazeError::NoStart)?;
+
+        // S may connect to any neighbour that connects back to it, but only some of those lead
+        // back around to S
+        [NORTH, EAST, SOUTH, WEST]
+            .into_iter()
+            .filter_map(|dir| {
+                let (tiles, end_dir) = follow_pipes(grid, start, dir)?;
+                Some(PipeLoop {
+                    tiles,
+                    start_pipe: pipe_with(dir | opposite(end_dir)),
+                })
+            })
+            .next()
+            .ok_or(MazeError::NoLoop)
+    }
+
+    fn neighbours(&self, i: usize) -> ((usize, usize), (usize, usize)) {
+        let len = self.tiles.len();
+        (self.tiles[(i + len - 1) % len], self.tiles[(i + 1) % len])
+    }
+
+    /// Returns the corners of the loop in order
+    fn vertices(&self) -> Vec<(usize, usize)> {
+        (0..self.tiles.len())
+            .filter(|&i| {
+                let (prev, next) = self.neighbours(i);
+                // Straight pipes have both neighbours in the same row or column
+                prev.0 != next.0 && prev.1 != next.1
+            })
+            .map(|i| self.tiles[i])
+            .collect()
+    }
+
+    /// Returns twice the area enclosed by the centre line of the loop, using the shoelace formula
+    fn doubled_area(&self) -> u64 {
+        let vertices = self.vertices();
+        let sum: i64 = vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .map(|(&(x1, y1), &(x2, y2))| x1 as i64 * y2 as i64 - x2 as i64 * y1 as i64)
+            .sum();
+        sum.unsigned_abs()
+    }
+
+    /// Returns the number of tiles enclosed by the loop, using Pick's theorem.
+    /// A = i + b/2 - 1, so i = A - b/2 + 1
+    fn enclosed_count(&self) -> u64 {
+        (self.doubled_area() - self.tiles.len() as u64 + 2) / 2
+    }
+
+    /// Returns the tiles enclosed by the loop, row by row
+    fn interior_tiles(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        // For each loop tile, whether it connects to the tile above it
+        let mut north = vec![vec![None; width]; height];
+        for (i, &(x, y)) in self.tiles.iter().enumerate() {
+            let (prev, next) = self.neighbours(i);
+            north[y][x] = Some(prev == (x, y.wrapping_sub(1)) || next == (x, y.wrapping_sub(1)));
+        }
+
+        // A ray along the bottom half of a row crosses the loop at every tile connected upwards
+        let mut interior = Vec::new();
+        for (y, row) in north.iter().enumerate() {
+            let mut inside = false;
+            for (x, &tile) in row.iter().enumerate() {
+                match tile {
+                    Some(true) => inside = !inside,
+                    Some(false) => (),
+                    None if inside => interior.push((x, y)),
+                    None => (),
+                }
+            }
+        }
+        interior
+    }
+}
+
+#[allow(dead_code)]
+pub fn part1() -> u32 {
+    let grid = INPUT.lines().map(|s| s.as_bytes()).collect::<Vec<_>>();
+    PipeLoop::trace(&grid).unwrap().tiles.len() as u32 / 2
+}
+
+fn find_start(grid: &[&[u8]]) -> Option<(usize, usize)> {
     for (i, row) in grid.iter().enumerate() {
         for (j, &cell) in row.iter().enumerate() {
             if cell == b'S' {
-                return (j, i);
+                return Some((j, i));
             }
         }
     }
-    unreachable!()
+    None
 }
 
-fn next_pipe(grid: &[&[u8]], x: usize, y: usize, prev_x: usize, prev_y: usize) -> (usize, usize) {
-    match grid[y][x] {
-        b'|' => {
-            if y > prev_y {
-                (x, y + 1)
-            } else {
-                (x, y - 1)
-            }
-        }
-        b'-' => {
-            if x > prev_x {
-                (x + 1, y)
-            } else {
-                (x - 1, y)
-            }
-        }
-        b'L' => {
-            if y > prev_y {
-                (x + 1, y)
-            } else {
-                (x, y - 1)
-            }
-        }
-        b'J' => {
-            if y > prev_y {
-                (x - 1, y)
-            } else {
-                (x, y - 1)
-            }
-        }
-        b'7' => {
-            if y < prev_y {
-                (x - 1, y)
-            } else {
-                (x, y + 1)
-            }
+/// Follows the pipes leaving `start` in `dir`. If they lead back to `start`, returns the tiles on
+/// the way and the direction they re-enter `start` in. Returns `None` on a dead end.
+fn follow_pipes(
+    grid: &[&[u8]],
+    start: (usize, usize),
+    mut dir: u8,
+) -> Option<(Vec<(usize, usize)>, u8)> {
+    let mut tiles = vec![start];
+    let (mut x, mut y) = start;
+    loop {
+        (x, y) = step(grid, x, y, dir)?;
+        if (x, y) == start {
+            return Some((tiles, dir));
         }
-        b'F' => {
-            if y < prev_y {
-                (x + 1, y)
-            } else {
-                (x, y + 1)
-            }
-        }
-        b'S' => {
-            // Left
-            if x > 0 {
-                let pipe = grid[y][x - 1];
-                if pipe == b'-' || pipe == b'L' || pipe == b'F' {
-                    return (x - 1, y);
-                }
-            }
-            // Right
-            if x < grid[y].len() - 1 {
-                let pipe = grid[y][x + 1];
-                if pipe == b'-' || pipe == b'J' || pipe == b'7' {
-                    return (x + 1, y);
-                }
-            }
-            // Up
-            if y > 0 {
-                let pipe = grid[y - 1][x];
-                if pipe == b'|' || pipe == b'7' || pipe == b'F' {
-                    return (x, y - 1);
-                }
-            }
-            // Down
-            if y < grid.len() - 1 {
-                let pipe = grid[y + 1][x];
-                if pipe == b'|' || pipe == b'J' || pipe == b'L' {
-                    return (x, y + 1);
-                }
-            }
-            unreachable!()
+
+        let pipe = connections(grid[y][x]);
+        if pipe & opposite(dir) == 0 {
+            return None;
         }
-        _ => unreachable!(),
+        tiles.push((x, y));
+        dir = pipe & !opposite(dir);
     }
 }
 
 #[allow(dead_code)]
 pub fn part2() -> u32 {
     let grid = INPUT.lines().map(|s| s.as_bytes()).collect::<Vec<_>>();
-    let mut loop_grid = repeat(repeat(b'O').take(grid[0].len()).collect::<Vec<_>>())
-        .take(grid.len())
-        .collect::<Vec<_>>();
+    let width = grid.iter().map(|row| row.len()).max().unwrap();
+    let mut loop_grid = vec![vec![b'O'; width]; grid.len()];
 
-    let (start_x, start_y) = find_start(&grid);
-    loop_grid[start_y][start_x] = start_type(&grid, start_x, start_y);
-    let (mut x, mut y) = next_pipe(&grid, start_x, start_y, start_x, start_y);
-    let mut prev_x = start_x;
-    let mut prev_y = start_y;
-
-    while start_x != x || start_y != y {
+    let pipe_loop = PipeLoop::trace(&grid).unwrap();
+    let (start_x, start_y) = pipe_loop.tiles[0];
+    loop_grid[start_y][start_x] = pipe_loop.start_pipe;
+    for &(x, y) in &pipe_loop.tiles[1..] {
         loop_grid[y][x] = grid[y][x];
-        let (next_x, next_y) = next_pipe(&grid, x, y, prev_x, prev_y);
-        prev_x = x;
-        prev_y = y;
-        x = next_x;
-        y = next_y;
     }
 
     let loop_grid = loop_grid.iter().map(|v| v.as_slice()).collect::<Vec<_>>();
-    (0..loop_grid.len())
+    let count = (0..loop_grid.len())
         .filter_map(|i| {
             if i % 2 == 0 {
                 Some(count_inside(&loop_grid, i))
@@ -145,36 +206,15 @@ pub fn part2() -> u32 {
                 None
             }
         })
-        .sum()
-}
-
-fn start_type(grid: &[&[u8]], x: usize, y: usize) -> u8 {
-    // Assume start is not at the edge
-    assert!(x > 0 && x < grid[y].len() - 1 && y > 0 && y < grid.len() - 1);
-
-    let left = grid[y][x - 1];
-    let right = grid[y][x + 1];
-    let up = grid[y - 1][x];
-    let down = grid[y + 1][x];
-    if left == b'-' || left == b'L' || left == b'F' {
-        if up == b'|' || up == b'7' || up == b'F' {
-            b'J'
-        } else if down == b'|' || down == b'J' || down == b'L' {
-            b'7'
-        } else {
-            b'-'
-        }
-    } else if right == b'-' || right == b'J' || right == b'7' {
-        if up == b'|' || up == b'7' || up == b'F' {
-            b'L'
-        } else if down == b'|' || down == b'J' || down == b'L' {
-            b'F'
-        } else {
-            b'-'
-        }
-    } else {
-        b'|'
-    }
+        .sum();
+
+    // Check the parity scan against the loop's geometry
+    debug_assert_eq!(count as u64, pipe_loop.enclosed_count());
+    debug_assert_eq!(
+        count as usize,
+        pipe_loop.interior_tiles(width, grid.len()).len()
+    );
+    count
 }
 
 fn count_inside(grid: &[&[u8]], row: usize) -> u32 {
@@ -186,7 +226,7 @@ fn count_inside(grid: &[&[u8]], row: usize) -> u32 {
     let bottom = grid[row + 1];
     let mut count = 0;
     let mut inside = false;
-    for i in 1..top.len() {
+    for i in 0..top.len() {
         if top[i] == b'|' || top[i] == b'7' || top[i] == b'F' {
             inside = !inside;
             continue;
@@ -203,3 +243,143 @@ fn count_inside(grid: &[&[u8]], row: usize) -> u32 {
 
     count
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tile {
+    /// A pipe on the main loop, with S replaced by the pipe under it
+    Loop(u8),
+    /// A pipe that isn't part of the main loop
+    Junk(u8),
+    Inside,
+    Outside,
+}
+
+/// Classifies every tile of the maze, padding ragged rows with outside tiles
+fn classify(grid: &[&[u8]]) -> Vec<Vec<Tile>> {
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut tiles: Vec<Vec<_>> = grid
+        .iter()
+        .map(|row| {
+            let mut row: Vec<_> = row
+                .iter()
+                .map(|&c| match connections(c) {
+                    0 => Tile::Outside,
+                    _ => Tile::Junk(c),
+                })
+                .collect();
+            row.resize(width, Tile::Outside);
+            row
+        })
+        .collect();
+
+    // Without a loop, everything is junk
+    if let Ok(pipe_loop) = PipeLoop::trace(grid) {
+        for &(x, y) in &pipe_loop.interior_tiles(width, grid.len()) {
+            tiles[y][x] = Tile::Inside;
+        }
+        for &(x, y) in &pipe_loop.tiles {
+            tiles[y][x] = Tile::Loop(grid[y][x]);
+        }
+        let (start_x, start_y) = pipe_loop.tiles[0];
+        tiles[start_y][start_x] = Tile::Loop(pipe_loop.start_pipe);
+    }
+    tiles
+}
+
+fn box_char(pipe: u8) -> char {
+    match pipe {
+        b'|' => '│',
+        b'-' => '─',
+        b'L' => '└',
+        b'J' => '┘',
+        b'7' => '┐',
+        b'F' => '┌',
+        _ => unreachable!(),
+    }
+}
+
+/// Redraws the maze with box-drawing characters for a terminal. The main loop is bold, junk pipes
+/// are dimmed, and enclosed tiles are marked with green dots.
+pub fn render_terminal(input: &str) -> String {
+    const RESET: &str = "\x1b[0m";
+
+    let grid = input.lines().map(|s| s.as_bytes()).collect::<Vec<_>>();
+    let mut out = String::new();
+    for row in classify(&grid) {
+        let mut style = RESET;
+        for tile in row {
+            let (next_style, c) = match tile {
+                Tile::Loop(pipe) => ("\x1b[1;33m", box_char(pipe)),
+                Tile::Junk(pipe) => ("\x1b[2m", box_char(pipe)),
+                Tile::Inside => ("\x1b[32m", '•'),
+                Tile::Outside => (RESET, ' '),
+            };
+            // Only switch styles when needed
+            if next_style != style {
+                out += RESET;
+                if next_style != RESET {
+                    out += next_style;
+                }
+                style = next_style;
+            }
+            out.push(c);
+        }
+        out += RESET;
+        out.push('\n');
+    }
+    out
+}
+
+/// Redraws the maze as an SVG image. The main loop is drawn thick, junk pipes are drawn thin and
+/// grey, and enclosed tiles are shaded green.
+pub fn render_svg(input: &str) -> String {
+    const CELL: usize = 10;
+
+    let grid = input.lines().map(|s| s.as_bytes()).collect::<Vec<_>>();
+    let tiles = classify(&grid);
+    let width = tiles.first().map_or(0, |row| row.len()) * CELL;
+    let height = tiles.len() * CELL;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n"
+    );
+    for (y, row) in tiles.iter().enumerate() {
+        for (x, &tile) in row.iter().enumerate() {
+            let (left, top) = (x * CELL, y * CELL);
+            let (pipe, stroke, stroke_width) = match tile {
+                Tile::Loop(pipe) => (pipe, "#d08000", 3),
+                Tile::Junk(pipe) => (pipe, "#bbbbbb", 1),
+                Tile::Inside => {
+                    svg += &format!(
+                        "<rect x=\"{left}\" y=\"{top}\" width=\"{CELL}\" height=\"{CELL}\" \
+                         fill=\"#88cc88\"/>\n"
+                    );
+                    continue;
+                }
+                Tile::Outside => continue,
+            };
+
+            // Draw a line from the centre of the tile to each side it connects to
+            let (cx, cy) = (left + CELL / 2, top + CELL / 2);
+            let conns = connections(pipe);
+            let mut path = String::new();
+            for (dir, (ex, ey)) in [
+                (NORTH, (cx, top)),
+                (EAST, (left + CELL, cy)),
+                (SOUTH, (cx, top + CELL)),
+                (WEST, (left, cy)),
+            ] {
+                if conns & dir != 0 {
+                    path += &format!("M{cx} {cy}L{ex} {ey}");
+                }
+            }
+            svg += &format!(
+                "<path d=\"{path}\" stroke=\"{stroke}\" stroke-width=\"{stroke_width}\" \
+                 stroke-linecap=\"square\"/>\n"
+            );
+        }
+    }
+    svg += "</svg>\n";
+    svg
+}
@@ -1,54 +1,256 @@
 const INPUT: &str = include_str!("day11.txt");
 
-fn parse_input(empty_size: u64) -> Vec<(u64, u64)> {
-    let map = INPUT
+/// A galaxy's position before expansion
+#[derive(Debug, Clone, Copy)]
+struct Galaxy {
+    x: u64,
+    y: u64,
+    /// The number of empty columns to the left of the galaxy
+    empty_cols: u64,
+    /// The number of empty rows above the galaxy
+    empty_rows: u64,
+}
+
+/// The galaxies, with expansion only applied when positions are needed so that huge expansion
+/// factors can't overflow
+#[derive(Debug)]
+struct Universe {
+    galaxies: Vec<Galaxy>,
+    empty_size: u64,
+}
+
+impl Universe {
+    fn position(&self, i: usize) -> (u128, u128) {
+        let g = self.galaxies[i];
+        let expand = |pos: u64, empty: u64| {
+            pos as u128 - empty as u128 + empty as u128 * self.empty_size as u128
+        };
+        (expand(g.x, g.empty_cols), expand(g.y, g.empty_rows))
+    }
+
+    fn positions(&self) -> Vec<(u128, u128)> {
+        (0..self.galaxies.len()).map(|i| self.position(i)).collect()
+    }
+
+    fn dist(&self, a: usize, b: usize) -> u128 {
+        let (a, b) = (self.position(a), self.position(b));
+        a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+    }
+
+    /// Returns the distance from galaxy `i` to every galaxy
+    fn dists_from(&self, i: usize) -> Vec<u128> {
+        (0..self.galaxies.len()).map(|j| self.dist(i, j)).collect()
+    }
+
+    /// Returns the galaxies sorted by expanded x, as (x, y, index)
+    fn sorted_by_x(&self) -> Vec<(u128, u128, usize)> {
+        let mut sorted: Vec<_> = self
+            .positions()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, y))| (x, y, i))
+            .collect();
+        sorted.sort_unstable();
+        sorted
+    }
+
+    /// Returns the two closest galaxies and the distance between them
+    fn closest_pair(&self) -> Option<(usize, usize, u128)> {
+        let sorted = self.sorted_by_x();
+        let mut best: Option<(usize, usize, u128)> = None;
+        for (i, &(x1, y1, a)) in sorted.iter().enumerate() {
+            for &(x2, y2, b) in &sorted[i + 1..] {
+                // Galaxies further right can only be further away
+                if best.is_some_and(|(_, _, dist)| x2 - x1 >= dist) {
+                    break;
+                }
+                let dist = x2 - x1 + y1.abs_diff(y2);
+                if best.is_none_or(|(_, _, best)| dist < best) {
+                    best = Some((a.min(b), a.max(b), dist));
+                }
+            }
+        }
+        best
+    }
+
+    /// Returns the two furthest galaxies and the distance between them
+    fn farthest_pair(&self) -> Option<(usize, usize, u128)> {
+        if self.galaxies.len() < 2 {
+            return None;
+        }
+
+        // |x1 - x2| + |y1 - y2| is the largest of |(x1 + y1) - (x2 + y2)| and
+        // |(x1 - y1) - (x2 - y2)|, so only the extremes of x + y and x - y matter
+        let positions = self.positions();
+        let extremes = |key: &dyn Fn((u128, u128)) -> i128| {
+            let keys = positions.iter().map(|&p| key(p));
+            let (min, _) = keys.clone().enumerate().min_by_key(|&(_, k)| k).unwrap();
+            let (max, _) = keys.enumerate().max_by_key(|&(_, k)| k).unwrap();
+            (min, max)
+        };
+        let sums = extremes(&|(x, y)| (x + y) as i128);
+        let diffs = extremes(&|(x, y)| x as i128 - y as i128);
+
+        [sums, diffs]
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b), self.dist(a, b)))
+            .max_by_key(|&(_, _, dist)| dist)
+    }
+
+    /// Returns the sum of the distances between every pair of galaxies
+    fn sum_pair_dists(&self) -> u128 {
+        // Manhattan distance is the sum of the distances along each axis
+        let (xs, ys) = self.positions().into_iter().unzip();
+        sum_abs_diffs(xs) + sum_abs_diffs(ys)
+    }
+
+    /// Returns the sum of the distances between pairs of galaxies at most `cutoff` apart
+    fn sum_pair_dists_within(&self, cutoff: u128) -> u128 {
+        let sorted = self.sorted_by_x();
+        let mut sum = 0;
+        for (i, &(x1, y1, _)) in sorted.iter().enumerate() {
+            for &(x2, y2, _) in &sorted[i + 1..] {
+                if x2 - x1 > cutoff {
+                    break;
+                }
+                let dist = x2 - x1 + y1.abs_diff(y2);
+                if dist <= cutoff {
+                    sum += dist;
+                }
+            }
+        }
+        sum
+    }
+}
+
+fn parse_input(input: &str, empty_size: u64) -> Universe {
+    let map = input
         .lines()
-        .map(|line| line.chars().collect::<Vec<_>>())
+        .map(|line| line.as_bytes())
         .collect::<Vec<_>>();
 
-    let mut x_dists = vec![0; map[0].len()];
+    // Number of empty columns/rows before each column/row
+    let mut empty_cols = vec![0; map[0].len()];
     for x in 1..map[0].len() {
-        let empty = (0..map.len()).all(|y| map[y][x] == '.');
-        x_dists[x] = x_dists[x - 1] + if empty { empty_size } else { 1 };
+        let empty = map.iter().all(|row| row[x - 1] == b'.');
+        empty_cols[x] = empty_cols[x - 1] + empty as u64;
     }
 
-    let mut y_dists = vec![0; map.len()];
+    let mut empty_rows = vec![0; map.len()];
     for y in 1..map.len() {
-        let empty = map[y].iter().all(|&c| c == '.');
-        y_dists[y] = y_dists[y - 1] + if empty { empty_size } else { 1 };
+        let empty = map[y - 1].iter().all(|&c| c == b'.');
+        empty_rows[y] = empty_rows[y - 1] + empty as u64;
     }
 
-    map.iter()
+    let galaxies = map
+        .iter()
         .enumerate()
         .flat_map(|(y, row)| {
             row.iter()
                 .enumerate()
-                .filter(|(_, &c)| c == '#')
-                .map(|(x, _)| (x_dists[x], y_dists[y]))
+                .filter(|(_, &c)| c == b'#')
+                .map(|(x, _)| Galaxy {
+                    x: x as u64,
+                    y: y as u64,
+                    empty_cols: empty_cols[x],
+                    empty_rows: empty_rows[y],
+                })
                 .collect::<Vec<_>>()
         })
-        .collect()
+        .collect();
+    Universe {
+        galaxies,
+        empty_size,
+    }
 }
 
-fn sum_pair_dists(galaxies: Vec<(u64, u64)>) -> u64 {
-    galaxies
+/// Returns the sum of |a - b| over all pairs of values
+fn sum_abs_diffs(mut values: Vec<u128>) -> u128 {
+    // Once sorted, each value is bigger than all the values before it
+    values.sort_unstable();
+    let mut prefix_sum = 0;
+    values
         .iter()
         .enumerate()
-        .map(|(i, g1)| {
-            galaxies[i + 1..]
-                .iter()
-                .map(|g2| g1.0.abs_diff(g2.0) + g1.1.abs_diff(g2.1))
-                .sum::<u64>()
+        .map(|(i, &v)| {
+            let diffs = v * i as u128 - prefix_sum;
+            prefix_sum += v;
+            diffs
         })
         .sum()
 }
 
+/// A linear function of the expansion factor, f(x) = slope * x + intercept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Linear {
+    slope: i128,
+    intercept: i128,
+}
+
+impl Linear {
+    /// Returns the line through two (expansion factor, sum of distances) points
+    fn fit((x1, y1): (u64, u128), (x2, y2): (u64, u128)) -> Self {
+        let (x1, y1, x2, y2) = (x1 as i128, y1 as i128, x2 as i128, y2 as i128);
+        let slope = (y2 - y1) / (x2 - x1);
+        Linear {
+            slope,
+            intercept: y1 - slope * x1,
+        }
+    }
+
+    fn at(&self, x: u64) -> i128 {
+        self.slope * x as i128 + self.intercept
+    }
+}
+
+/// Prints the sum of distances between galaxies for an expansion factor, along with the sum as a
+/// function of any expansion factor and the closest and farthest pairs. Also prints the distances
+/// from galaxy `from` (counting from 1) and the sum of distances at most `cutoff`, if given.
+pub fn print_pair_dists(input: &str, empty_size: u64, from: Option<usize>, cutoff: Option<u128>) {
+    // Every distance grows linearly with the expansion factor, and so does their sum
+    let dists = |size| parse_input(input, size).sum_pair_dists();
+    let linear = Linear::fit((1, dists(1)), (2, dists(2)));
+    debug_assert_eq!(linear.at(empty_size), dists(empty_size) as i128);
+
+    let universe = parse_input(input, empty_size);
+    println!("Sum of distances: {}", universe.sum_pair_dists());
+    println!(
+        "For any expansion factor x: {} * x + {}",
+        linear.slope, linear.intercept
+    );
+    if let (Some(closest), Some(farthest)) = (universe.closest_pair(), universe.farthest_pair()) {
+        for (name, (a, b, dist)) in [("Closest", closest), ("Farthest", farthest)] {
+            println!(
+                "{name} pair: galaxies {} and {} at distance {dist}",
+                a + 1,
+                b + 1
+            );
+        }
+    }
+
+    if let Some(cutoff) = cutoff {
+        println!(
+            "Sum of distances at most {cutoff}: {}",
+            universe.sum_pair_dists_within(cutoff)
+        );
+    }
+    match from {
+        Some(from) if (1..=universe.galaxies.len()).contains(&from) => {
+            for (i, dist) in universe.dists_from(from - 1).into_iter().enumerate() {
+                println!("Galaxy {from} to galaxy {}: {dist}", i + 1);
+            }
+        }
+        Some(from) => println!("There is no galaxy {from}"),
+        None => {}
+    }
+}
+
 #[allow(dead_code)]
-pub fn part1() -> u64 {
-    sum_pair_dists(parse_input(2))
+pub fn part1() -> u128 {
+    parse_input(INPUT, 2).sum_pair_dists()
 }
 
 #[allow(dead_code)]
-pub fn part2() -> u64 {
-    sum_pair_dists(parse_input(1_000_000))
+pub fn part2() -> u128 {
+    parse_input(INPUT, 1_000_000).sum_pair_dists()
 }
@@ -1,4 +1,9 @@
-use std::{collections::HashMap, iter::repeat};
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    iter::repeat_n,
+};
 
 const INPUT: &str = include_str!("day12.txt");
 
@@ -9,129 +14,445 @@ enum Spring {
     Unknown,
 }
 
+impl TryFrom<char> for Spring {
+    type Error = ();
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Spring::Operational),
+            '#' => Ok(Spring::Damaged),
+            '?' => Ok(Spring::Unknown),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Spring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Spring::Operational => '.',
+            Spring::Damaged => '#',
+            Spring::Unknown => '?',
+        };
+        write!(f, "{c}")
+    }
+}
+
+/// Parses a record of springs followed by the runs of damaged springs, such as `???.### 1,1,3`
+fn parse_record(line: &str) -> Option<(Vec<Spring>, Vec<u8>)> {
+    let (springs, runs) = line.split_once(' ')?;
+    let springs = springs
+        .chars()
+        .map(Spring::try_from)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let runs = runs
+        .split(',')
+        .map(|run| run.parse().ok())
+        .collect::<Option<_>>()?;
+    Some((springs, runs))
+}
+
 fn parse_input() -> Vec<(Vec<Spring>, Vec<u8>)> {
     INPUT
         .lines()
-        .map(|line| {
-            let (springs, runs) = line.split_once(' ').unwrap();
-            let springs = springs
-                .chars()
-                .map(|c| match c {
-                    '.' => Spring::Operational,
-                    '#' => Spring::Damaged,
-                    '?' => Spring::Unknown,
-                    _ => unreachable!(),
-                })
-                .collect();
-            let runs = runs.split(',').map(|run| run.parse().unwrap()).collect();
-            (springs, runs)
-        })
+        .map(|line| parse_record(line).unwrap())
         .collect()
 }
 
-fn count_possibilities(
+/// Repeats the springs `factor` times with unknown springs between them, and the runs `factor`
+/// times
+fn unfold(springs: &[Spring], runs: &[u8], factor: usize) -> (Vec<Spring>, Vec<u8>) {
+    let springs = repeat_n([Spring::Unknown].iter().chain(springs), factor)
+        .flatten()
+        .skip(1)
+        .copied()
+        .collect();
+    let runs = repeat_n(runs, factor).flatten().copied().collect();
+    (springs, runs)
+}
+
+/// Returns the number of operational springs before each spring, to check runs in constant time
+fn operational_before(springs: &[Spring]) -> Vec<usize> {
+    let mut operational = vec![0; springs.len() + 1];
+    for (i, &spring) in springs.iter().enumerate() {
+        operational[i + 1] = operational[i] + (spring == Spring::Operational) as usize;
+    }
+    operational
+}
+
+/// Returns the end of a run starting at `start`, if the run fits there and can be followed by an
+/// operational spring
+fn run_end(springs: &[Spring], operational: &[usize], start: usize, run: u8) -> Option<usize> {
+    let end = start + run as usize;
+    (springs[start] != Spring::Operational
+        && end <= springs.len()
+        && operational[end] == operational[start]
+        && springs.get(end) != Some(&Spring::Damaged))
+    .then_some(end)
+}
+
+/// Returns a table where `table[i * (runs.len() + 1) + j]` combines the arrangements of
+/// `springs[i..]` with `runs[j..]`, given that `springs[i - 1]` isn't part of a run. `done` is the
+/// value of an empty arrangement and `none` of an impossible one.
+fn suffix_table<T: Copy>(
     springs: &[Spring],
     runs: &[u8],
-    mut prev_damaged: bool,
-    seen: &mut HashMap<u16, u64>,
-) -> u64 {
-    let hash = (springs.len() | runs.len() << 8).try_into().unwrap();
-    let hash = if prev_damaged { hash | 1 << 15 } else { hash };
-    if let Some(&count) = seen.get(&hash) {
-        return count;
-    }
-
-    let mut runs_idx = 0;
-    let mut run = 0;
-    if prev_damaged {
-        if runs_idx >= runs.len() {
-            return 0;
+    none: T,
+    done: T,
+    combine: impl Fn(T, T) -> T,
+) -> Vec<T> {
+    let (n, m) = (springs.len(), runs.len());
+    let operational = operational_before(springs);
+    let mut table = vec![none; (n + 1) * (m + 1)];
+    table[n * (m + 1) + m] = done;
+    for i in (0..n).rev() {
+        for j in 0..=m {
+            let mut value = none;
+            if springs[i] != Spring::Damaged {
+                value = combine(value, table[(i + 1) * (m + 1) + j]);
+            }
+            // Start the next run here, followed by an operational spring
+            if let Some(end) = runs
+                .get(j)
+                .and_then(|&run| run_end(springs, &operational, i, run))
+            {
+                value = combine(value, table[(end + 1).min(n) * (m + 1) + j + 1]);
+            }
+            table[i * (m + 1) + j] = value;
         }
-        run = runs[runs_idx] - 1;
-        runs_idx += 1;
     }
+    table
+}
 
-    for i in 0..springs.len() {
-        match springs[i] {
-            Spring::Operational => {
-                // Expected a damaged spring but got an operational one
-                if run > 0 {
-                    return 0;
-                }
-                prev_damaged = false;
+/// The number of arrangements of every suffix of the springs with every suffix of the runs
+#[derive(Debug)]
+struct Counts {
+    /// `table[i * (runs + 1) + j]` is the number of arrangements of `springs[i..]` with
+    /// `runs[j..]`, given that `springs[i - 1]` isn't part of a run
+    table: Vec<u128>,
+    runs: usize,
+}
+
+impl Counts {
+    fn new(springs: &[Spring], runs: &[u8]) -> Self {
+        Counts {
+            table: suffix_table(springs, runs, 0, 1, |a, b| a + b),
+            runs: runs.len(),
+        }
+    }
+
+    fn get(&self, springs_idx: usize, runs_idx: usize) -> u128 {
+        self.table[springs_idx * (self.runs + 1) + runs_idx]
+    }
+
+    fn total(&self) -> u128 {
+        self.table[0]
+    }
+}
+
+fn count_possibilities(springs: &[Spring], runs: &[u8]) -> u128 {
+    Counts::new(springs, runs).total()
+}
+
+/// Returns the springs with every spring that is the same in all arrangements filled in, or
+/// `None` if there are no arrangements. Springs that differ between arrangements are unknown.
+fn solve_line(springs: &[Spring], runs: &[u8]) -> Option<Vec<Spring>> {
+    let (n, m) = (springs.len(), runs.len());
+    // Only whether there is an arrangement matters, and counts could overflow on long lines
+    let fits_after = suffix_table(springs, runs, false, true, |a, b| a || b);
+    if !fits_after[0] {
+        return None;
+    }
+
+    // The same table for the reversed line says whether `springs[..i]` fits `runs[..j]`
+    let reversed: Vec<_> = springs.iter().rev().copied().collect();
+    let reversed_runs: Vec<_> = runs.iter().rev().copied().collect();
+    let fits_before_rev = suffix_table(&reversed, &reversed_runs, false, true, |a, b| a || b);
+    let fits_after = |i: usize, j: usize| fits_after[i * (m + 1) + j];
+    // Whether `springs[..i]` fits `runs[..j]` with `springs[i - 1]` not part of a run
+    let fits_before = |i: usize, j: usize| match i {
+        0 => j == 0,
+        _ => springs[i - 1] != Spring::Damaged && fits_before_rev[(n - i + 1) * (m + 1) + m - j],
+    };
+
+    let operational = operational_before(springs);
+    let mut can_be_operational = vec![false; n];
+    // Marks the start and end of each range that can be damaged
+    let mut damaged_ranges = vec![0i32; n + 1];
+    for i in 0..n {
+        for j in (0..=m).filter(|&j| fits_before(i, j)) {
+            if springs[i] != Spring::Damaged && fits_after(i + 1, j) {
+                can_be_operational[i] = true;
             }
-            Spring::Damaged => {
-                if run == 0 {
-                    // Ran out of damaged runs when there are still damaged springs
-                    if runs_idx >= runs.len() {
-                        return 0;
-                    }
-                    // Reached end of damaged run but there is another damaged spring
-                    if prev_damaged {
-                        return 0;
-                    }
-                    // Start the next damaged run
-                    run = runs[runs_idx];
-                    runs_idx += 1;
+            if let Some(end) = runs
+                .get(j)
+                .and_then(|&run| run_end(springs, &operational, i, run))
+                .filter(|&end| fits_after((end + 1).min(n), j + 1))
+            {
+                damaged_ranges[i] += 1;
+                damaged_ranges[end] -= 1;
+                if end < n {
+                    can_be_operational[end] = true;
                 }
-                prev_damaged = true;
-                run -= 1;
             }
-            Spring::Unknown => {
-                // Run is ongoing, this one must be damaged
-                if run > 0 {
-                    prev_damaged = true;
-                    run -= 1;
-                    continue;
-                }
-                // Run just ended, this one must be operational
-                if prev_damaged {
-                    prev_damaged = false;
-                    continue;
+        }
+    }
+
+    let mut damaged = 0;
+    let solved = (0..n)
+        .map(|i| {
+            damaged += damaged_ranges[i];
+            match (damaged > 0, can_be_operational[i]) {
+                (true, true) => Spring::Unknown,
+                (true, false) => Spring::Damaged,
+                (false, _) => Spring::Operational,
+            }
+        })
+        .collect();
+    Some(solved)
+}
+
+/// The arrangements of one row, ordered with operational springs before damaged ones. Counts
+/// for every suffix are kept, so any arrangement can be found directly.
+struct Arrangements<'a> {
+    springs: &'a [Spring],
+    runs: &'a [u8],
+    counts: Counts,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(springs: &'a [Spring], runs: &'a [u8]) -> Self {
+        Arrangements {
+            springs,
+            runs,
+            counts: Counts::new(springs, runs),
+        }
+    }
+
+    fn count(&self) -> u128 {
+        self.counts.total()
+    }
+
+    /// Returns the `k`th arrangement, counting from 0, without building the ones before it
+    fn nth(&self, mut k: u128) -> Option<Vec<Spring>> {
+        if k >= self.count() {
+            return None;
+        }
+
+        let n = self.springs.len();
+        let mut arrangement = Vec::with_capacity(n);
+        let mut runs_idx = 0;
+        while arrangement.len() < n {
+            let i = arrangement.len();
+            // Skip every arrangement where this one is operational if there are too few of them
+            let operational = if self.springs[i] == Spring::Damaged {
+                0
+            } else {
+                self.counts.get(i + 1, runs_idx)
+            };
+            if k < operational {
+                arrangement.push(Spring::Operational);
+                continue;
+            }
+
+            // Only arrangements that fit the runs are ever picked, so the run fits here
+            k -= operational;
+            let end = i + self.runs[runs_idx] as usize;
+            arrangement.resize(end, Spring::Damaged);
+            if end < n {
+                arrangement.push(Spring::Operational);
+            }
+            runs_idx += 1;
+        }
+        Some(arrangement)
+    }
+
+    /// Returns every arrangement in order, one at a time
+    fn iter(&self) -> impl Iterator<Item = Vec<Spring>> + '_ {
+        (0..self.count()).map(|k| self.nth(k).unwrap())
+    }
+
+    /// Returns an arrangement picked uniformly at random, using `random` for random u64s
+    fn sample(&self, random: &mut impl FnMut() -> u64) -> Option<Vec<Spring>> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        // Reject the top values that would make some arrangements more likely than others
+        let limit = u128::MAX - u128::MAX % count;
+        let k = loop {
+            let value = (random() as u128) << 64 | random() as u128;
+            if value < limit {
+                break value % count;
+            }
+        };
+        self.nth(k)
+    }
+}
+
+/// A nonogram, where every row and column is a record of damaged springs
+#[derive(Debug)]
+struct Nonogram {
+    rows: Vec<Vec<u8>>,
+    cols: Vec<Vec<u8>>,
+}
+
+impl Nonogram {
+    /// Parses the row clues, a blank line and then the column clues, one line per row or column.
+    /// Each line is a comma-separated list of runs, or 0 if there are none.
+    fn parse(input: &str) -> Option<Self> {
+        let parse_clues = |clues: &str| -> Option<Vec<Vec<u8>>> {
+            clues
+                .lines()
+                .map(|line| {
+                    line.split(',')
+                        .map(|run| run.trim().parse().ok())
+                        .filter(|&run| run != Some(0))
+                        .collect()
+                })
+                .collect()
+        };
+        let (rows, cols) = input.trim_end().split_once("\n\n")?;
+        Some(Nonogram {
+            rows: parse_clues(rows)?,
+            cols: parse_clues(cols)?,
+        })
+    }
+
+    /// Solves every row and column on its own until nothing changes. Returns false if some row or
+    /// column has no arrangements.
+    fn propagate(&self, grid: &mut [Spring]) -> bool {
+        let width = self.cols.len();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, runs) in self.rows.iter().enumerate() {
+                let row = &mut grid[y * width..(y + 1) * width];
+                let Some(solved) = solve_line(row, runs) else {
+                    return false;
+                };
+                changed |= row != solved;
+                row.copy_from_slice(&solved);
+            }
+            for (x, runs) in self.cols.iter().enumerate() {
+                let col: Vec<_> = grid.iter().skip(x).step_by(width).copied().collect();
+                let Some(solved) = solve_line(&col, runs) else {
+                    return false;
+                };
+                changed |= col != solved;
+                for (y, spring) in solved.into_iter().enumerate() {
+                    grid[y * width + x] = spring;
                 }
+            }
+        }
+        true
+    }
 
-                // Undecided, try both possibilities
-                let result = count_possibilities(&springs[i + 1..], &runs[runs_idx..], false, seen)
-                    + count_possibilities(&springs[i + 1..], &runs[runs_idx..], true, seen);
-                seen.insert(hash, result);
-                return result;
+    /// Returns a solution, or `None` if there isn't one. When solving each line on its own gets
+    /// stuck, the first unknown cell is guessed and the guess is undone if it leads nowhere.
+    fn solve(&self, mut grid: Vec<Spring>) -> Option<Vec<Spring>> {
+        if !self.propagate(&mut grid) {
+            return None;
+        }
+        let Some(guess) = grid.iter().position(|&spring| spring == Spring::Unknown) else {
+            return Some(grid);
+        };
+        [Spring::Damaged, Spring::Operational]
+            .into_iter()
+            .find_map(|spring| {
+                let mut grid = grid.clone();
+                grid[guess] = spring;
+                self.solve(grid)
+            })
+    }
+}
+
+/// Prints the solution to a nonogram, with damaged cells as `#` and operational cells as `.`
+pub fn print_nonogram(input: &str) {
+    let Some(nonogram) = Nonogram::parse(input) else {
+        eprintln!("Expected row clues, a blank line and column clues");
+        return;
+    };
+    let width = nonogram.cols.len();
+    let grid = vec![Spring::Unknown; nonogram.rows.len() * width];
+    match nonogram.solve(grid) {
+        Some(grid) => {
+            for row in grid.chunks(width.max(1)) {
+                let row: String = row.iter().map(Spring::to_string).collect();
+                println!("{row}");
             }
         }
+        None => println!("No solution"),
     }
+}
+
+/// Which arrangements of a record to print
+pub enum Pick {
+    All,
+    /// The arrangement at this index, counting from 0
+    Nth(u128),
+    /// An arrangement picked uniformly at random
+    Sample,
+}
 
-    let result = if run > 0 || runs_idx < runs.len() {
-        // There are still damaged runs left, but no more damaged springs
-        0
-    } else {
-        // No unknowns, only one possibility
-        1
+/// Prints the number of arrangements of a record, followed by the picked arrangements
+pub fn print_arrangements(record: &str, pick: Pick) {
+    let Some((springs, runs)) = parse_record(record) else {
+        eprintln!("Expected springs and runs, such as \"???.### 1,1,3\"");
+        return;
     };
-    seen.insert(hash, result);
-    result
+    let arrangements = Arrangements::new(&springs, &runs);
+    println!("{} arrangements", arrangements.count());
+
+    let print = |arrangement: Vec<Spring>| {
+        println!(
+            "{}",
+            arrangement
+                .iter()
+                .map(Spring::to_string)
+                .collect::<String>()
+        );
+    };
+    match pick {
+        Pick::All => arrangements.iter().for_each(print),
+        Pick::Nth(k) => match arrangements.nth(k) {
+            Some(arrangement) => print(arrangement),
+            None => println!("There is no arrangement {k}"),
+        },
+        Pick::Sample => {
+            // Hashers are randomly seeded, which is random enough without pulling in a crate
+            let state = RandomState::new();
+            let mut counter = 0u64;
+            let mut random = || {
+                let mut hasher = state.build_hasher();
+                hasher.write_u64(counter);
+                counter += 1;
+                hasher.finish()
+            };
+            if let Some(arrangement) = arrangements.sample(&mut random) {
+                print(arrangement);
+            }
+        }
+    }
 }
 
 #[allow(dead_code)]
-pub fn part1() -> u64 {
+pub fn part1() -> u128 {
     parse_input()
-        .into_iter()
-        .map(|(springs, runs)| count_possibilities(&springs, &runs, false, &mut HashMap::new()))
+        .iter()
+        .map(|(springs, runs)| count_possibilities(springs, runs))
         .sum()
 }
 
 #[allow(dead_code)]
-pub fn part2() -> u64 {
+pub fn part2() -> u128 {
     parse_input()
-        .into_iter()
+        .iter()
         .map(|(springs, runs)| {
-            (
-                repeat([Spring::Unknown].into_iter().chain(springs))
-                    .take(5)
-                    .flatten()
-                    .skip(1)
-                    .collect::<Vec<_>>(),
-                repeat(runs).take(5).flatten().collect::<Vec<_>>(),
-            )
+            let (springs, runs) = unfold(springs, runs, 5);
+            count_possibilities(&springs, &runs)
         })
-        .map(|(springs, runs)| count_possibilities(&springs, &runs, false, &mut HashMap::new()))
         .sum()
 }
@@ -1,105 +1,253 @@
+use std::ops::Range;
+
 const INPUT: &str = include_str!("day13.txt");
 
-fn parse_input() -> Vec<(Vec<u32>, Vec<u32>)> {
-    INPUT
-        .split("\n\n")
-        .map(|pattern| {
-            let rows = pattern
-                .lines()
-                .map(|row| {
-                    row.bytes()
-                        .enumerate()
-                        .map(|(i, c)| match c {
-                            b'#' => 1 << i,
-                            b'.' => 0,
-                            _ => unreachable!(),
-                        })
-                        .sum::<u32>()
-                        | (1 << row.len()) // Add leading 1
-                })
-                .collect::<Vec<_>>();
-            let width = u32::BITS - rows[0].leading_zeros() - 1;
-            let cols = (0..width)
-                .map(|i| {
-                    rows.iter()
-                        .map(|row| (row >> i) & 1)
-                        .fold(0, |acc, x| (acc << 1) | x)
-                        | (1 << rows.len()) // Add leading 1
-                })
-                .collect::<Vec<_>>();
-            (rows, cols)
-        })
-        .collect()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MirrorError {
+    NoAxis,
+    /// More than one axis fits, with how many there are
+    Ambiguous(usize),
 }
 
-#[allow(dead_code)]
-pub fn part1() -> usize {
-    parse_input()
-        .iter()
-        .map(|(rows, cols)| 100 * find_mirror(rows) + find_mirror(cols))
-        .sum()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// Between two rows
+    Horizontal,
+    /// Between two columns
+    Vertical,
 }
 
-fn find_mirror(pattern: &[u32]) -> usize {
-    'outer: for i in 1..pattern.len() {
-        if pattern[i] != pattern[i - 1] {
-            continue;
+/// An axis of reflection, along with the cells that had to be repaired for it to fit
+#[derive(Debug)]
+struct Axis {
+    direction: Direction,
+    /// The number of rows above or columns left of the axis
+    position: usize,
+    /// The range of rows or columns that are reflected
+    span: Range<usize>,
+    /// The (row, column) of each smudge on the top or left side, and of its reflection
+    smudges: Vec<((usize, usize), (usize, usize))>,
+}
+
+/// Every row or every column of a pattern as a bitset, so that patterns of any size fit
+#[derive(Debug)]
+struct Lines {
+    /// Line `i` is `bits[i * words..(i + 1) * words]`
+    bits: Vec<u64>,
+    words: usize,
+}
+
+impl Lines {
+    fn new(count: usize, len: usize) -> Self {
+        let words = len.div_ceil(u64::BITS as usize).max(1);
+        Lines {
+            bits: vec![0; count * words],
+            words,
         }
+    }
+
+    fn len(&self) -> usize {
+        self.bits.len() / self.words
+    }
 
-        let len = i.min(pattern.len() - i);
-        for j in 1..len {
-            if pattern[i + j] != pattern[i - j - 1] {
-                continue 'outer;
+    fn set(&mut self, line: usize, i: usize) {
+        self.bits[line * self.words + i / 64] |= 1 << (i % 64);
+    }
+
+    fn line(&self, i: usize) -> &[u64] {
+        &self.bits[i * self.words..(i + 1) * self.words]
+    }
+
+    /// Returns the number of cells that differ between two lines
+    fn diff(&self, a: usize, b: usize) -> u32 {
+        self.line(a)
+            .iter()
+            .zip(self.line(b))
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+
+    /// Returns the positions of the cells that differ between two lines
+    fn diff_cells(&self, a: usize, b: usize) -> Vec<usize> {
+        let mut cells = Vec::new();
+        for (i, (a, b)) in self.line(a).iter().zip(self.line(b)).enumerate() {
+            let mut diff = a ^ b;
+            while diff != 0 {
+                cells.push(i * 64 + diff.trailing_zeros() as usize);
+                diff &= diff - 1;
             }
         }
-        return i;
+        cells
     }
-    0
-}
 
-#[allow(dead_code)]
-pub fn part2() -> usize {
-    parse_input()
-        .into_iter()
-        .map(|(mut rows, mut cols)| {
-            100 * find_mirror_with_smudge(&mut rows) + find_mirror_with_smudge(&mut cols)
-        })
-        .sum()
+    /// Returns the range of lines reflected by the axis between lines `axis - 1` and `axis`
+    fn span(&self, axis: usize) -> Range<usize> {
+        let len = axis.min(self.len() - axis);
+        axis - len..axis + len
+    }
+
+    /// Returns every axis where exactly `smudges` cells differ from their reflection. Axis `i` is
+    /// between lines `i - 1` and `i`.
+    fn mirrors(&self, smudges: u32) -> Vec<usize> {
+        let len = self.len();
+        (1..len)
+            .filter(|&i| {
+                let mut diffs = 0;
+                for j in 0..i.min(len - i) {
+                    diffs += self.diff(i - j - 1, i + j);
+                    if diffs > smudges {
+                        return false;
+                    }
+                }
+                diffs == smudges
+            })
+            .collect()
+    }
 }
 
-fn find_mirrors_all(pattern: &[u32]) -> Vec<usize> {
-    let mut mirrors = Vec::new();
-    'outer: for i in 1..pattern.len() {
-        if pattern[i] != pattern[i - 1] {
-            continue;
-        }
+#[derive(Debug)]
+struct Pattern {
+    rows: Lines,
+    cols: Lines,
+}
 
-        let len = i.min(pattern.len() - i);
-        for j in 1..len {
-            if pattern[i + j] != pattern[i - j - 1] {
-                continue 'outer;
+impl Pattern {
+    fn parse(pattern: &str) -> Self {
+        let height = pattern.lines().count();
+        // Rows shorter than the longest one are padded with ash
+        let width = pattern.lines().map(str::len).max().unwrap_or(0);
+        let mut rows = Lines::new(height, width);
+        let mut cols = Lines::new(width, height);
+        for (y, row) in pattern.lines().enumerate() {
+            for (x, c) in row.bytes().enumerate() {
+                match c {
+                    b'#' => {
+                        rows.set(y, x);
+                        cols.set(x, y);
+                    }
+                    b'.' => {}
+                    _ => unreachable!(),
+                }
             }
         }
-        mirrors.push(i);
+        Pattern { rows, cols }
+    }
+
+    /// Returns every axis, horizontal then vertical, that is off by exactly `smudges` cells
+    fn axes(&self, smudges: u32) -> Vec<Axis> {
+        let find = |lines: &Lines, direction| -> Vec<Axis> {
+            lines
+                .mirrors(smudges)
+                .into_iter()
+                .map(|position| {
+                    let span = lines.span(position);
+                    let smudges = (span.start..position)
+                        .flat_map(|line| {
+                            let reflection = 2 * position - line - 1;
+                            lines
+                                .diff_cells(line, reflection)
+                                .into_iter()
+                                .map(move |cell| match direction {
+                                    Direction::Horizontal => ((line, cell), (reflection, cell)),
+                                    Direction::Vertical => ((cell, line), (cell, reflection)),
+                                })
+                        })
+                        .collect();
+                    Axis {
+                        direction,
+                        position,
+                        span,
+                        smudges,
+                    }
+                })
+                .collect()
+        };
+        let mut axes = find(&self.rows, Direction::Horizontal);
+        axes.extend(find(&self.cols, Direction::Vertical));
+        axes
+    }
+
+    /// Returns 100 times the first horizontal axis plus the first vertical axis that are off by
+    /// exactly `smudges` cells, which is how the puzzle scores patterns
+    fn score(&self, smudges: u32) -> usize {
+        let first = |lines: &Lines| lines.mirrors(smudges).first().copied().unwrap_or(0);
+        100 * first(&self.rows) + first(&self.cols)
     }
-    mirrors
 }
 
-fn find_mirror_with_smudge(pattern: &mut [u32]) -> usize {
-    let mirror = find_mirror(pattern);
+/// Checks that there is exactly one axis
+fn check_unique(axes: &[Axis]) -> Result<(), MirrorError> {
+    match axes.len() {
+        0 => Err(MirrorError::NoAxis),
+        1 => Ok(()),
+        count => Err(MirrorError::Ambiguous(count)),
+    }
+}
 
-    for i in 0..pattern.len() {
-        for j in 0..u32::BITS - pattern[i].leading_zeros() - 1 {
-            pattern[i] ^= 1 << j;
-            let mirrors = find_mirrors_all(pattern);
-            pattern[i] ^= 1 << j;
+fn parse_input(input: &str) -> Vec<Pattern> {
+    input.split("\n\n").map(Pattern::parse).collect()
+}
 
-            for m in mirrors {
-                if m != mirror {
-                    return m;
-                }
+/// Prints every axis of each pattern that is off by exactly `smudges` cells, with the cells that
+/// were repaired. Patterns without exactly one axis are flagged, but are still scored the same way
+/// as the puzzle so that the total is the puzzle's answer.
+pub fn print_mirror_report(input: &str, smudges: u32) {
+    let mut total = 0;
+    let mut flagged = 0;
+    for (i, pattern) in parse_input(input).iter().enumerate() {
+        let axes = pattern.axes(smudges);
+        let score = pattern.score(smudges);
+        total += score;
+        match check_unique(&axes) {
+            Ok(()) => println!("Pattern {}: score {score}", i + 1),
+            Err(err) => {
+                flagged += 1;
+                let reason = match err {
+                    MirrorError::NoAxis => "no axis".to_owned(),
+                    MirrorError::Ambiguous(count) => format!("{count} axes"),
+                };
+                println!("Pattern {}: score {score}, FLAGGED, {reason}", i + 1);
+            }
+        }
+
+        for axis in axes {
+            let lines = match axis.direction {
+                Direction::Horizontal => "rows",
+                Direction::Vertical => "columns",
+            };
+            println!(
+                "  {:?} axis after {} {lines}, reflecting {lines} {}-{}",
+                axis.direction,
+                axis.position,
+                axis.span.start + 1,
+                axis.span.end,
+            );
+            for ((row, col), (mirror_row, mirror_col)) in axis.smudges {
+                println!(
+                    "    Repaired row {}, column {} (mirrors row {}, column {})",
+                    row + 1,
+                    col + 1,
+                    mirror_row + 1,
+                    mirror_col + 1,
+                );
             }
         }
     }
-    0
+    println!("Total: {total}, with {flagged} patterns flagged");
+}
+
+#[allow(dead_code)]
+pub fn part1() -> usize {
+    parse_input(INPUT)
+        .iter()
+        .map(|pattern| pattern.score(0))
+        .sum()
+}
+
+#[allow(dead_code)]
+pub fn part2() -> usize {
+    parse_input(INPUT)
+        .iter()
+        .map(|pattern| pattern.score(1))
+        .sum()
 }
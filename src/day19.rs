@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 
+use crate::interval_set::IntervalSet;
+
 const INPUT: &str = include_str!("day19.txt");
 
 type Workflow = [(Condition, &'static str); 4];
 type WorkflowMap = HashMap<&'static str, Workflow>;
 type Part = [u16; 4];
-// Includes start, excludes end
-type PartsRange = [(u16, u16); 4];
+type PartsRange = [IntervalSet<u16>; 4];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Condition {
@@ -116,33 +117,25 @@ fn count_matched(workflows: &WorkflowMap, workflow_name: &str, mut range: PartsR
         return 0;
     }
     if workflow_name == "A" {
-        return range
-            .iter()
-            .map(|(start, end)| (end - start) as u64)
-            .product();
+        return range.iter().map(|r| r.len() as u64).product();
     }
 
     let workflow = workflows.get(workflow_name).unwrap();
     let mut count = 0;
 
     for rule in workflow {
-        let mut matched = range;
-        match rule.0 {
-            Condition::True => (),
-            Condition::Lt(cat, tresh) => {
-                matched[cat as usize].1 = tresh;
-                range[cat as usize].0 = tresh;
+        let (cat, accepted): (_, IntervalSet<_>) = match rule.0 {
+            Condition::True => {
+                count += count_matched(workflows, rule.1, range);
+                break;
             }
-            Condition::Gt(cat, tresh) => {
-                matched[cat as usize].0 = tresh + 1;
-                range[cat as usize].1 = tresh + 1;
-            }
-        }
+            Condition::Lt(cat, tresh) => (cat as usize, (0..tresh).into()),
+            Condition::Gt(cat, tresh) => (cat as usize, (tresh + 1..u16::MAX).into()),
+        };
+        let mut matched = range.clone();
+        matched[cat] = range[cat].intersection(&accepted);
+        range[cat] = range[cat].difference(&accepted);
         count += count_matched(workflows, rule.1, matched);
-
-        if rule.0 == Condition::True {
-            break;
-        }
     }
 
     count
@@ -151,5 +144,9 @@ fn count_matched(workflows: &WorkflowMap, workflow_name: &str, mut range: PartsR
 #[allow(dead_code)]
 pub fn part2() -> u64 {
     let (workflows, _) = parse_input();
-    count_matched(&workflows, "in", [(1, 4001); 4])
+    count_matched(
+        &workflows,
+        "in",
+        std::array::from_fn(|_| IntervalSet::from(1..4001)),
+    )
 }
@@ -0,0 +1,153 @@
+use std::{
+    fmt::Debug,
+    ops::{Add, Range, Sub},
+};
+
+/// A set of values stored as sorted, disjoint, non-adjacent, non-empty ranges.
+/// Ranges include start and exclude end.
+#[derive(Clone, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T> IntervalSet<T>
+where
+    T: Copy + Ord + Default + Add<Output = T> + Sub<Output = T>,
+{
+    pub fn new() -> Self {
+        IntervalSet { ranges: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the number of values in the set
+    pub fn len(&self) -> T {
+        self.ranges
+            .iter()
+            .fold(T::default(), |len, r| len + (r.end - r.start))
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::coalesce(self.ranges.iter().chain(&other.ranges).cloned().collect())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+        for r in &self.ranges {
+            let mut start = r.start;
+            // Skip ranges that end before this one starts
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < r.end {
+                let cut = &other.ranges[k];
+                if start < cut.start {
+                    ranges.push(start..cut.start);
+                }
+                start = start.max(cut.end);
+                k += 1;
+            }
+            if start < r.end {
+                ranges.push(start..r.end);
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    /// Shifts every value so that `from` maps to `to`. Panics if any value
+    /// would go out of bounds.
+    pub fn translate(&self, from: T, to: T) -> Self {
+        let shift = |x: T| {
+            if to >= from {
+                x + (to - from)
+            } else {
+                x - (from - to)
+            }
+        };
+        IntervalSet {
+            ranges: self
+                .ranges
+                .iter()
+                .map(|r| shift(r.start)..shift(r.end))
+                .collect(),
+        }
+    }
+
+    /// Sorts `ranges`, drops empty ranges, and merges overlapping or adjacent
+    /// ranges
+    fn coalesce(mut ranges: Vec<Range<T>>) -> Self {
+        ranges.retain(|r| r.start < r.end);
+        ranges.sort_unstable_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<T>> = Vec::with_capacity(ranges.len());
+        for r in ranges {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => merged.push(r),
+            }
+        }
+        IntervalSet { ranges: merged }
+    }
+}
+
+impl<T> Default for IntervalSet<T>
+where
+    T: Copy + Ord + Default + Add<Output = T> + Sub<Output = T>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Range<T>> for IntervalSet<T>
+where
+    T: Copy + Ord + Default + Add<Output = T> + Sub<Output = T>,
+{
+    fn from(range: Range<T>) -> Self {
+        Self::coalesce(vec![range])
+    }
+}
+
+impl<T> FromIterator<Range<T>> for IntervalSet<T>
+where
+    T: Copy + Ord + Default + Add<Output = T> + Sub<Output = T>,
+{
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        Self::coalesce(iter.into_iter().collect())
+    }
+}
+
+impl<T: Debug> Debug for IntervalSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(&self.ranges).finish()
+    }
+}
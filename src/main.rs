@@ -23,10 +23,149 @@ mod day22;
 mod day23;
 mod day24;
 mod day25;
+mod interval_set;
 
-use std::{fmt::Display, time::Instant};
+use std::{env, fmt::Display, fs, time::Instant};
+
+const USAGE: &str = "\
+Usage: aoc_2023 [OPTION]
+Runs and times every day when no option is given.
+
+Options:
+  --card-copies [--points] [--wrap] [--max <copies>] <input file>
+                                         Print how many copies of each day 4 card are won
+  --compose-maps <input file>            Print the day 5 seed to location map
+  --boat-races [--accel <n>] [--delay <ms>] [--max-speed <n>] <input file>
+                                         Print the best hold and winning holds of each day 6 race
+  --rank-hands <1|2> <input file>        Print the day 7 ranking of every hand
+  --network-dot [--cycles] <input file>  Print the day 8 network in DOT format
+  --extrapolate <input file>             Print the degree and next readings of each day 9 history
+  --render-pipes [--svg] <input file>    Draw the day 10 pipe maze for a terminal or as SVG
+  --galaxy-dists <factor> [--from <galaxy>] [--within <cutoff>] <input file>
+                                         Sum day 11 galaxy distances and find the closest and
+                                         farthest pairs, distances from one galaxy and the sum of
+                                         distances within a cutoff
+  --arrangements <record> [<k>|--sample] Print every day 12 arrangement of a record such as
+                                         '???.### 1,1,3', only the kth, or a random one
+  --nonogram <clues file>                Solve a nonogram given row clues, a blank line and column clues
+  --mirror-report <smudges> <input file> List every day 13 mirror axis and the smudges it repairs";
 
 fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => run_all(),
+        ["--card-copies", ref options @ .., path] => match copy_rules(options) {
+            Some((points, wrap, max)) => day04::print_copy_counts(&read(path), points, wrap, max),
+            None => eprintln!("{USAGE}"),
+        },
+        ["--compose-maps", path] => day05::print_composed(&read(path)),
+        ["--boat-races", ref options @ .., path] => match boat_options(options) {
+            Some((accel, delay, max_speed)) => {
+                day06::print_races(&read(path), accel, delay, max_speed)
+            }
+            None => eprintln!("{USAGE}"),
+        },
+        ["--rank-hands", part @ ("1" | "2"), path] => {
+            day07::print_ranking(&read(path), part == "2")
+        }
+        ["--network-dot", path] => print!(
+            "{}",
+            day08::to_dot(
+                &read(path),
+                day08::is_ghost_start,
+                day08::is_ghost_end,
+                false
+            )
+        ),
+        ["--network-dot", "--cycles", path] => print!(
+            "{}",
+            day08::to_dot(
+                &read(path),
+                day08::is_ghost_start,
+                day08::is_ghost_end,
+                true
+            )
+        ),
+        ["--extrapolate", path] => day09::print_extrapolations(&read(path)),
+        ["--render-pipes", path] => print!("{}", day10::render_terminal(&read(path))),
+        ["--render-pipes", "--svg", path] => print!("{}", day10::render_svg(&read(path))),
+        ["--galaxy-dists", factor, ref options @ .., path] => {
+            match (factor.parse(), galaxy_options(options)) {
+                (Ok(factor), Some((from, cutoff))) => {
+                    day11::print_pair_dists(&read(path), factor, from, cutoff)
+                }
+                _ => eprintln!("{USAGE}"),
+            }
+        }
+        ["--mirror-report", smudges, path] => match smudges.parse() {
+            Ok(smudges) => day13::print_mirror_report(&read(path), smudges),
+            Err(_) => eprintln!("{USAGE}"),
+        },
+        ["--arrangements", record] => day12::print_arrangements(record, day12::Pick::All),
+        ["--arrangements", record, "--sample"] => {
+            day12::print_arrangements(record, day12::Pick::Sample)
+        }
+        ["--arrangements", record, k] => match k.parse() {
+            Ok(k) => day12::print_arrangements(record, day12::Pick::Nth(k)),
+            Err(_) => eprintln!("{USAGE}"),
+        },
+        ["--nonogram", path] => day12::print_nonogram(&read(path)),
+        _ => eprintln!("{USAGE}"),
+    }
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+/// Parses the day 4 options into whether copies scale with points, whether they wrap and the
+/// maximum copies of a card
+fn copy_rules(options: &[&str]) -> Option<(bool, bool, Option<u128>)> {
+    let (mut points, mut wrap, mut max) = (false, false, None);
+    let mut options = options.iter();
+    while let Some(&option) = options.next() {
+        match option {
+            "--points" => points = true,
+            "--wrap" => wrap = true,
+            "--max" => max = Some(options.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((points, wrap, max))
+}
+
+/// Parses the day 6 options into the boat's acceleration, delay and maximum speed
+fn boat_options(options: &[&str]) -> Option<(u64, u64, Option<u64>)> {
+    let (mut accel, mut delay, mut max_speed) = (1, 0, None);
+    let mut options = options.iter();
+    while let Some(&option) = options.next() {
+        let value = options.next()?.parse().ok()?;
+        match option {
+            "--accel" => accel = value,
+            "--delay" => delay = value,
+            "--max-speed" => max_speed = Some(value),
+            _ => return None,
+        }
+    }
+    Some((accel, delay, max_speed))
+}
+
+/// Parses the day 11 options into the galaxy to list distances from and the distance cutoff
+fn galaxy_options(options: &[&str]) -> Option<(Option<usize>, Option<u128>)> {
+    let (mut from, mut cutoff) = (None, None);
+    let mut options = options.iter();
+    while let Some(&option) = options.next() {
+        let value = options.next()?;
+        match option {
+            "--from" => from = Some(value.parse().ok()?),
+            "--within" => cutoff = Some(value.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((from, cutoff))
+}
+
+fn run_all() {
     let start = Instant::now();
 
     time(1, 1, day01::part1);